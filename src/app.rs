@@ -1,52 +1,110 @@
 use std::env::{Args, self};
 
-use legion::World;
+use legion::{World, IntoQuery, Resources, Schedule};
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder, dpi::PhysicalSize,
+    window::{WindowBuilder, Window}, dpi::PhysicalSize,
 };
 
 use crate::{
-    renderer::Renderer, 
-    text::TextBoxFactory, camera::Camera
+    error::Error, renderer::Renderer, 
+    text::{TextBoxFactory, RASTER_SIZE}, camera::Camera, time::{FrameLimiter, Time, update_time_system}, debug_overlay::DebugOverlay
 };
 
-fn initialize_world(renderer: &mut Renderer, world: &mut World) {
-    let text_factory = TextBoxFactory::new(renderer).unwrap();
+//rasterizes the default text size at the display's physical pixel size
+fn set_text_scale_factor(renderer: &mut Renderer, text_factory: &mut TextBoxFactory, scale_factor: f64) -> Result<(), Error> {
+    text_factory.set_scale_factor(scale_factor);
+    text_factory.cache_size(renderer, (RASTER_SIZE as f64 * scale_factor).round() as u32)?;
+    Ok(())
+}
+
+fn initialize_world(renderer: &mut Renderer, world: &mut World, window: &Window) -> Result<TextBoxFactory, Error> {
+    let mut text_factory = TextBoxFactory::new(renderer)?;
+    set_text_scale_factor(renderer, &mut text_factory, window.scale_factor())?;
+
+    //create the camera
+    let size = window.inner_size();
+    let camera = Camera::new(size.width, size.height)
+        .scale_factor(window.scale_factor());
+
+    //world units are logical points, so place the label from the camera rather than the window
+    let (width, height) = camera.logical_size();
     let text_components = text_factory
-        .create("ChatGPT Conversation (dogs)", (800f32, 600f32), 0.9, 1f32);
+        .create("ChatGPT Conversation (dogs)", (width / 2f32, height / 2f32), 0.9, 1f32);
     world.extend(text_components);
 
-    let file = env::args().skip(1).next().expect("Expected a file to be passed!");
+    let file = env::args().nth(1).ok_or(Error::MissingFileArgument)?;
     println!("file {}", file);
 
-    //create the camera
-    let camera = Camera::new(1600, 1200);
-
     world.push((camera,));
+
+    Ok(text_factory)
+}
+
+//there's nothing to fall back to when startup fails, so report it and quit
+fn or_exit<T>(result: Result<T, Error>, context: &str) -> T {
+    match result {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("{}: {}", context, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn resize(renderer: &mut Renderer, world: &mut World, new_size: PhysicalSize<u32>) {
+    renderer.resize(new_size);
+
+    if new_size.width == 0 || new_size.height == 0 { return }
+
+    let mut camera_query = <&mut Camera>::query();
+    for camera in camera_query.iter_mut(world) {
+        camera.resize(new_size);
+    }
 }
 
-pub fn run() {
+//target_fps caps the frame rate independent of the present mode, None runs uncapped
+pub fn run(target_fps: Option<u32>) {
     env_logger::init();
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new()
+    let window = or_exit(WindowBuilder::new()
         .with_inner_size(PhysicalSize::<u32> { width: 1600, height: 1200 })
-        .build(&event_loop).unwrap();
+        .build(&event_loop)
+        .map_err(Error::from), "Couldn't open a window");
 
-    let mut renderer = Renderer::new(&window);
+    let mut renderer = or_exit(Renderer::new(&window), "Couldn't start the renderer");
     let mut world = World::default();
 
-    initialize_world(&mut renderer, &mut world);
+    let mut text_factory = or_exit(initialize_world(&mut renderer, &mut world, &window), "Couldn't set up the world");
+
+    let mut frame_limiter = FrameLimiter::new(target_fps);
+    //systems read the frame clock from resources, it's updated first thing every frame
+    let mut resources = Resources::default();
+    resources.insert(Time::new());
+    let mut schedule = Schedule::builder()
+        .add_system(update_time_system())
+        .build();
+    let mut debug_overlay = or_exit(DebugOverlay::new(&mut renderer), "Couldn't create the debug overlay");
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
         } if window_id == window.id() => match event {
-            WindowEvent::Resized(new_size) => renderer.resize(*new_size),
-            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                renderer.resize(**new_inner_size)
+            WindowEvent::Resized(new_size) => resize(&mut renderer, &mut world, *new_size),
+            WindowEvent::ScaleFactorChanged { new_inner_size, scale_factor } => {
+                let mut camera_query = <&mut Camera>::query();
+                for camera in camera_query.iter_mut(&mut world) {
+                    camera.set_scale_factor(*scale_factor);
+                }
+
+                //text keeps drawing from the nearest size it already has if the new one can't be made
+                if let Err(e) = set_text_scale_factor(&mut renderer, &mut text_factory, *scale_factor) {
+                    eprintln!("Couldn't rasterize text for the new scale factor: {}", e);
+                }
+
+                resize(&mut renderer, &mut world, **new_inner_size)
             }
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
@@ -58,15 +116,38 @@ pub fn run() {
                     },
                 ..
             } => *control_flow = ControlFlow::Exit,
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F3),
+                        ..
+                    },
+                ..
+            } => {
+                debug_overlay.toggle(&mut world);
+                renderer.enable_gpu_timings(debug_overlay.enabled());
+            }
             _ => {}
         },
         Event::RedrawRequested(_) => {
+            schedule.execute(&mut world, &mut resources);
+            let time = resources.get::<Time>().expect("Time is inserted at startup");
+
+            let view_size = <&Camera>::query().iter(&world)
+                .next()
+                .map(|camera| camera.logical_size())
+                .unwrap_or_default();
+            let gpu_time = renderer.gpu_timings().map(|timings| timings.total());
+            debug_overlay.update(&mut world, &time, view_size, gpu_time);
+
             match renderer.render(&world) {
                 Ok(_) => {}
-                Err(wgpu::SurfaceError::Lost) => renderer.find_display(),
                 Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
                 Err(e) => eprintln!("{:?}", e),
             }
+
+            frame_limiter.wait();
         }
         Event::MainEventsCleared => {
             window.request_redraw();
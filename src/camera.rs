@@ -16,7 +16,9 @@ pub struct Camera {
     bottom: f32,
     left: f32,
     width: f32,
-    height: f32
+    height: f32,
+    //physical pixels per world unit, so world units match logical points
+    scale_factor: f32,
 }
 
 impl Camera {
@@ -37,10 +39,25 @@ impl Camera {
             bottom,
             left,
             width,
-            height
+            height,
+            scale_factor: 1.0
         }
     }
 
+    //width and height passed to new are treated as physical pixels
+    pub fn scale_factor(mut self, scale_factor: f64) -> Self {
+        self.set_scale_factor(scale_factor); self
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        let scale_factor = scale_factor as f32;
+        self.width *= self.scale_factor / scale_factor;
+        self.height *= self.scale_factor / scale_factor;
+        self.scale_factor = scale_factor;
+    }
+
+    pub fn logical_size(&self) -> (f32, f32) { (self.width, self.height) }
+
     pub fn matrix(&self) -> cgmath::Matrix4<f32> {
             let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
             // let view = cgmath::Matrix4::identity();
@@ -56,11 +73,23 @@ impl Camera {
             OPENGL_TO_WGPU_MATRIX * proj * view
     }
 
-    //this should be moved to some kind of camera controller
+    //keeps the eye where it is so world positions stay put on screen
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.width = new_size.width as f32;
-        self.height = new_size.height as f32;
-        self.eye = Point3::<f32> { x: self.width / 2.0, y: self.height / 2.0, z: 100.0 };
-        self.target = Point3::<f32> { x: self.width / 2.0, y: self.height / 2.0, z: 0.0 };
+        self.width = new_size.width as f32 / self.scale_factor;
+        self.height = new_size.height as f32 / self.scale_factor;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_factor_halves_the_logical_size() {
+        let mut camera = Camera::new(1600, 1200).scale_factor(2.0);
+        assert_eq!(camera.logical_size(), (800f32, 600f32));
+
+        camera.resize(PhysicalSize::new(1000, 500));
+        assert_eq!(camera.logical_size(), (500f32, 250f32));
+    }
+}
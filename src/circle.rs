@@ -0,0 +1,74 @@
+use simple_error::SimpleError;
+
+use crate::{error::Error, pipeline::Pipeline, renderer::{Renderer, MaterialHandle, RenderStage}, graphics::{Rectangle, RectangleBuilder, Vertex}, ui_box::hex_color};
+
+//circles are drawn as a quad with the edge computed in the shader, so they stay smooth at any size
+pub struct CircleFactory {
+    material_handle: MaterialHandle
+}
+
+impl CircleFactory {
+    pub fn new(renderer: &mut Renderer) -> Result<Self, Error> {
+        let pipeline = Pipeline::load::<Vertex>(include_str!("circle.wgsl"))?;
+        let pipeline_handle = renderer.create_pipeline(pipeline)?;
+        let material_handle = renderer.create_material(pipeline_handle)?;
+
+        Ok(Self {
+            material_handle
+        })
+    }
+
+    pub fn create(&self, color: &str, center: (f32, f32), radius: f32, depth: f32) 
+        -> Result<(Rectangle, MaterialHandle, RenderStage), SimpleError> 
+    {
+        self.create_ellipse(color, (center.0 - radius, center.1 - radius), (radius * 2f32, radius * 2f32), depth)
+    }
+
+    //the ellipse fills the box it's given, touching each edge at its middle
+    pub fn create_ellipse(&self, color: &str, position: (f32, f32), size: (f32, f32), depth: f32) 
+        -> Result<(Rectangle, MaterialHandle, RenderStage), SimpleError> 
+    {
+        let color = hex_color(color)?;
+
+        let rectangle = RectangleBuilder::default()
+            .position(position.0, position.1)
+            .size(size.0, size.1)
+            .color(color)
+            .depth(depth)
+            .build();
+
+        Ok((rectangle, self.material_handle, RenderStage { order: 0 }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::World;
+
+    use super::*;
+    use crate::camera::Camera;
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn circle_fills_its_quad_with_a_smooth_edge() {
+        let mut renderer = Renderer::headless(64, 64).expect("Gpu tests need a graphics adapter");
+        let circles = CircleFactory::new(&mut renderer).unwrap();
+
+        let mut world = World::default();
+        world.push((Camera::new(64, 64),));
+        world.push(circles.create("#ffffff", (32f32, 32f32), 32f32, 0.5).unwrap());
+
+        renderer.capture_frame_image();
+        renderer.render(&world).unwrap();
+        let image = renderer.take_capture_result()
+            .expect("A frame was rendered")
+            .unwrap();
+
+        assert_eq!(image.get_pixel(32, 32).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+
+        //the left edge of the circle runs through the middle of this pixel
+        let edge = image.get_pixel(0, 32).0[0];
+        assert!(edge > 0 && edge < 255, "edge pixel should be partially covered, was {}", edge);
+    }
+}
@@ -0,0 +1,45 @@
+// Vertex shader
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) local_position: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view_proj * vec4<f32>(model.position, 1.0);
+    out.color = model.color;
+    // -1 to 1 across the quad, so a non square quad gives an ellipse
+    out.local_position = model.tex_coords * 2.0 - 1.0;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the edge sits at 0, fwidth keeps it about a pixel wide at any size
+    let distance = length(in.local_position) - 1.0;
+    let edge_width = fwidth(distance);
+    let coverage = 1.0 - smoothstep(-edge_width, 0.0, distance);
+
+    // outside the edge would otherwise still write depth and hide what's behind the corners
+    if coverage <= 0.0 {
+        discard;
+    }
+
+    return vec4<f32>(in.color, coverage);
+}
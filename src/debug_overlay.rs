@@ -0,0 +1,92 @@
+use std::{collections::VecDeque, time::Duration};
+
+use legion::{World, Entity};
+use crate::{error::Error, renderer::Renderer, text::TextBoxFactory, time::Time};
+
+//number of frames the fps and frame time are averaged over
+const AVERAGED_FRAMES: usize = 60;
+//how often the text is rebuilt, so the numbers are readable
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+const MARGIN: f32 = 8f32;
+const TEXT_SCALE: f32 = 0.4;
+//the overlay isn't depth tested, this only has to be inside the camera's depth range
+const DEPTH: f32 = 0.95;
+
+pub struct DebugOverlay {
+    text_factory: TextBoxFactory,
+    enabled: bool,
+    frame_times: VecDeque<Duration>,
+    since_refresh: Duration,
+    entities: Vec<Entity>,
+}
+
+impl DebugOverlay {
+    pub fn new(renderer: &mut Renderer) -> Result<Self, Error> {
+        Ok(Self {
+            text_factory: TextBoxFactory::new_overlay(renderer)?,
+            enabled: false,
+            frame_times: VecDeque::with_capacity(AVERAGED_FRAMES),
+            since_refresh: REFRESH_INTERVAL,
+            entities: Vec::new(),
+        })
+    }
+
+    pub fn enabled(&self) -> bool { self.enabled }
+
+    pub fn toggle(&mut self, world: &mut World) {
+        self.set_enabled(world, !self.enabled);
+    }
+
+    pub fn set_enabled(&mut self, world: &mut World, enabled: bool) {
+        self.enabled = enabled;
+        //show the numbers on the next update instead of waiting a full interval
+        self.since_refresh = REFRESH_INTERVAL;
+
+        if !enabled {
+            self.clear(world);
+        }
+    }
+
+    pub fn average_frame_time(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::ZERO
+        }
+
+        self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+    }
+
+    //call once per frame after time has been updated, view_size is the camera's logical size.
+    //gpu_time is shown next to the cpu frame time when the renderer is timing passes
+    pub fn update(&mut self, world: &mut World, time: &Time, view_size: (f32, f32), gpu_time: Option<Duration>) {
+        if self.frame_times.len() == AVERAGED_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(time.delta());
+
+        if !self.enabled { return }
+
+        self.since_refresh += time.delta();
+        if self.since_refresh < REFRESH_INTERVAL { return }
+        self.since_refresh = Duration::ZERO;
+
+        let frame_time = self.average_frame_time().as_secs_f32();
+        let fps = if frame_time > 0f32 { 1f32 / frame_time } else { 0f32 };
+        let mut text = format!("{:.0} fps  {:.2} ms", fps, frame_time * 1000f32);
+        if let Some(gpu_time) = gpu_time {
+            text += &format!("  gpu {:.2} ms", gpu_time.as_secs_f32() * 1000f32);
+        }
+
+        let line_height = self.text_factory.font(TEXT_SCALE).line_height(TEXT_SCALE);
+        let position = (MARGIN, view_size.1 - line_height - MARGIN);
+
+        self.clear(world);
+        self.entities = world.extend(self.text_factory.create(&text, position, DEPTH, TEXT_SCALE)).to_vec();
+    }
+
+    fn clear(&mut self, world: &mut World) {
+        for entity in self.entities.drain(..) {
+            world.remove(entity);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use simple_error::SimpleError;
+
+//failures a caller might want to report rather than crash on, everything else still uses SimpleError
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Failed to read {path}: {source}")]
+    Io { path: String, source: std::io::Error },
+
+    #[error("Failed to decode image: {0}")]
+    ImageDecode(#[from] image::ImageError),
+
+    #[error("Failed to parse shader:\n{0}")]
+    ShaderParse(String),
+
+    #[error("Failed to create the window: {0}")]
+    CreateWindow(#[from] winit::error::OsError),
+
+    #[error("Expected a file to be passed as the first argument")]
+    MissingFileArgument,
+
+    #[error("Failed to create a surface for the window: {0}")]
+    CreateSurface(#[from] wgpu::CreateSurfaceError),
+
+    #[error("No compatible graphics adapter was found")]
+    NoAdapter,
+
+    #[error("Failed to open the graphics device: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+
+    #[error("Shader uses push constants but the graphics adapter doesn't support them")]
+    PushConstantsUnsupported,
+
+    #[error("Shader uses binding arrays but the graphics adapter doesn't support them")]
+    BindingArraysUnsupported,
+
+    #[error("{0}")]
+    Other(#[from] SimpleError),
+}
@@ -9,6 +9,12 @@ pub struct Vertex {
 impl Vertex {
     const ATTRIBS: [wgpu::VertexAttribute; 3] =
         wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+
+    pub fn new(position: [f32; 3], color: [f32; 3], tex_coords: [f32; 2]) -> Self {
+        Self { position, color, tex_coords }
+    }
+
+    pub fn position(&self) -> [f32; 3] { self.position }
 }
 
 impl crate::pipeline::Vertex for Vertex {
@@ -29,13 +35,15 @@ pub struct RectangleBuilder {
     width: f32, 
     height: f32, 
     depth: f32, 
-    color: [f32; 3],
+    //the same unless the rectangle has a vertical gradient
+    color_top: [f32; 3],
+    color_bottom: [f32; 3],
     tex_coords: [[f32; 2]; 4]
 }
 
 impl Default for RectangleBuilder {
     fn default() -> Self {
-        Self { x: 0.0, y: 0.0, width: 1.0, height: 1.0, depth: 0.0, color: [1.0, 1.0, 1.0], tex_coords: [[0.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]] }
+        Self { x: 0.0, y: 0.0, width: 1.0, height: 1.0, depth: 0.0, color_top: [1.0, 1.0, 1.0], color_bottom: [1.0, 1.0, 1.0], tex_coords: [[0.0, 1.0], [0.0, 0.0], [1.0, 1.0], [1.0, 0.0]] }
     }
 }
 
@@ -53,7 +61,16 @@ impl RectangleBuilder {
     }
 
     pub fn color(mut self, color: [f32; 3]) -> Self {
-        self.color = color; self
+        self.color_top = color; self.color_bottom = color; self
+    }
+
+    //colors are interpolated between the top and bottom edges
+    pub fn color_top(mut self, color: [f32; 3]) -> Self {
+        self.color_top = color; self
+    }
+
+    pub fn color_bottom(mut self, color: [f32; 3]) -> Self {
+        self.color_bottom = color; self
     }
 
     pub fn tex_coords(mut self, tex_coords: [[f32; 2]; 4]) -> Self {
@@ -65,13 +82,13 @@ impl RectangleBuilder {
         Rectangle {
             vertices: [
                 //bottom left
-                Vertex { position: [self.x,            self.y,             self.depth], color: self.color, tex_coords: self.tex_coords[0] }, 
+                Vertex { position: [self.x,            self.y,             self.depth], color: self.color_bottom, tex_coords: self.tex_coords[0] }, 
                 //top left
-                Vertex { position: [self.x,            self.y+self.height, self.depth], color: self.color, tex_coords: self.tex_coords[1] }, 
+                Vertex { position: [self.x,            self.y+self.height, self.depth], color: self.color_top,    tex_coords: self.tex_coords[1] }, 
                 //bottom right
-                Vertex { position: [self.x+self.width, self.y,             self.depth], color: self.color, tex_coords: self.tex_coords[2] }, 
+                Vertex { position: [self.x+self.width, self.y,             self.depth], color: self.color_bottom, tex_coords: self.tex_coords[2] }, 
                 //top right
-                Vertex { position: [self.x+self.width, self.y+self.height, self.depth], color: self.color, tex_coords: self.tex_coords[3] }, 
+                Vertex { position: [self.x+self.width, self.y+self.height, self.depth], color: self.color_top,    tex_coords: self.tex_coords[3] }, 
             ]
         }
     }
@@ -85,4 +102,35 @@ pub struct Rectangle {
 impl Rectangle {
     //bl, br, tl, tl, br, tr
     pub const INDICES: [u32; 6] = [0, 2, 1, 1, 2, 3];
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [f32; 3] = [1.0, 0.0, 0.0];
+    const BLUE: [f32; 3] = [0.0, 0.0, 1.0];
+
+    #[test]
+    fn gradient_colors_the_top_and_bottom_edges() {
+        let rectangle = RectangleBuilder::default()
+            .color_top(RED)
+            .color_bottom(BLUE)
+            .build();
+
+        let colors = rectangle.vertices.map(|vertex| vertex.color);
+        assert_eq!(colors, [BLUE, RED, BLUE, RED]);
+        assert_ne!(colors[1], colors[0]);
+        assert_ne!(colors[3], colors[2]);
+    }
+
+    #[test]
+    fn color_sets_every_vertex() {
+        let rectangle = RectangleBuilder::default()
+            .color_top(RED)
+            .color(BLUE)
+            .build();
+
+        assert_eq!(rectangle.vertices.map(|vertex| vertex.color), [BLUE; 4]);
+    }
+}
@@ -0,0 +1,113 @@
+use std::f32::consts::TAU;
+
+use crate::graphics::{Rectangle, RectangleBuilder, Vertex};
+
+//segments used to approximate a circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+//shapes queued up during a frame, every shape is built out of four vertex quads so the
+//renderer can draw them alongside everything else in one batch
+#[derive(Default)]
+pub struct ImmediateDraw {
+    shapes: Vec<Rectangle>,
+}
+
+impl ImmediateDraw {
+    pub fn line(&mut self, a: (f32, f32), b: (f32, f32), width: f32, color: [f32; 3]) {
+        let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+        if length == 0f32 { return }
+
+        //offset to the left of the line, half the width on either side
+        let normal = (-(b.1 - a.1) / length * width / 2f32, (b.0 - a.0) / length * width / 2f32);
+
+        self.shapes.push(quad([
+            [a.0 - normal.0, a.1 - normal.1],
+            [a.0 + normal.0, a.1 + normal.1],
+            [b.0 - normal.0, b.1 - normal.1],
+            [b.0 + normal.0, b.1 + normal.1],
+        ], color));
+    }
+
+    pub fn rect(&mut self, position: (f32, f32), size: (f32, f32), color: [f32; 3]) {
+        self.shapes.push(RectangleBuilder::default()
+            .position(position.0, position.1)
+            .size(size.0, size.1)
+            .color(color)
+            .build());
+    }
+
+    //each segment is a triangle from the center, the second triangle of its quad is left degenerate
+    pub fn circle(&mut self, center: (f32, f32), radius: f32, color: [f32; 3]) {
+        let point = |segment: usize| {
+            let angle = segment as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+            [center.0 + radius * angle.cos(), center.1 + radius * angle.sin()]
+        };
+
+        for segment in 0..CIRCLE_SEGMENTS {
+            let start = point(segment);
+            let end = point(segment + 1);
+            self.shapes.push(quad([[center.0, center.1], end, start, start], color));
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<Rectangle> {
+        std::mem::take(&mut self.shapes)
+    }
+}
+
+//corners in the same order RectangleBuilder uses: bottom left, top left, bottom right, top right
+fn quad(corners: [[f32; 2]; 4], color: [f32; 3]) -> Rectangle {
+    Rectangle {
+        vertices: corners.map(|[x, y]| Vertex::new([x, y, 0f32], color, [0f32, 0f32]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+    #[test]
+    fn lines_are_quads_centered_on_the_line() {
+        let mut draw = ImmediateDraw::default();
+        draw.line((0f32, 0f32), (10f32, 0f32), 2f32, WHITE);
+
+        let shapes = draw.take();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(shapes[0].vertices.map(|vertex| vertex.position()), [
+            [0f32, -1f32, 0f32],
+            [0f32, 1f32, 0f32],
+            [10f32, -1f32, 0f32],
+            [10f32, 1f32, 0f32],
+        ]);
+    }
+
+    #[test]
+    fn circles_are_a_fan_around_the_center() {
+        let mut draw = ImmediateDraw::default();
+        draw.circle((5f32, 5f32), 3f32, WHITE);
+
+        let shapes = draw.take();
+        assert_eq!(shapes.len(), CIRCLE_SEGMENTS);
+
+        for shape in shapes {
+            let [center, rim @ ..] = shape.vertices.map(|vertex| vertex.position());
+            assert_eq!(center, [5f32, 5f32, 0f32]);
+
+            for [x, y, _] in rim {
+                let radius = ((x - 5f32).powi(2) + (y - 5f32).powi(2)).sqrt();
+                assert!((radius - 3f32).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn taking_clears_the_queue() {
+        let mut draw = ImmediateDraw::default();
+        draw.rect((0f32, 0f32), (1f32, 1f32), WHITE);
+
+        assert_eq!(draw.take().len(), 1);
+        assert!(draw.take().is_empty());
+    }
+}
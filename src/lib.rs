@@ -1,6 +1,7 @@
 #![feature(option_as_slice)]
 
 pub mod app;
+pub mod error;
 pub mod graphics;
 pub mod renderer;
 pub mod pipeline;
@@ -8,4 +9,10 @@ pub mod shader_types;
 pub mod material;
 pub mod text;
 pub mod ui_box;
-pub mod camera;
\ No newline at end of file
+pub mod circle;
+pub mod camera;
+pub mod time;
+pub mod debug_overlay;
+pub mod immediate;
+#[cfg(feature = "shaping")]
+pub mod shaping;
\ No newline at end of file
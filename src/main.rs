@@ -1,5 +1,5 @@
 use sd_land::app;
 
 fn main() {
-    app::run();
+    app::run(Some(120));
 }
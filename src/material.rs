@@ -1,43 +1,119 @@
+use std::collections::HashMap;
+
+use simple_error::SimpleError;
+
 use crate::shader_types::MaterialValue;
 
 pub struct Material {
     uniforms: Vec<(String, (u32, u32), MaterialValue)>,
+    dynamic_uniforms: HashMap<String, Vec<MaterialValue>>,
 }
 
 impl Material {
     pub fn new(uniforms: Vec<(String, (u32, u32), MaterialValue)>) -> Self {
         Self {
-            uniforms
+            uniforms,
+            dynamic_uniforms: HashMap::new(),
+        }
+    }
+
+    //each dynamic uniform starts with a single instance holding the default value
+    pub fn dynamic_uniforms(mut self, names: &[String]) -> Self {
+        for name in names {
+            if let Some((_, _, value)) = self.uniforms.iter()
+                .find(|(uniform_name, _, _)| uniform_name == name)
+            {
+                self.dynamic_uniforms.insert(name.clone(), vec![value.clone()]);
+            }
         }
+        self
     }
 
     pub fn uniforms(&self) -> &Vec<(String, (u32, u32), MaterialValue)> {
         &self.uniforms
     }
 
-    pub fn uniform<T: 'static>(mut self, name: &str, value: T) -> Self {
-        self.set_uniform(name, value);
-        self
+    pub fn dynamic_instances(&self) -> &HashMap<String, Vec<MaterialValue>> {
+        &self.dynamic_uniforms
     }
 
-    pub fn set_uniform<T: 'static>(&mut self, name: &str, value: T) -> bool{
-        if let Some((_, (..), current_value)) = self.uniforms.iter_mut()
-            .find(|(uniform_name, _, _)| uniform_name == name) 
-        {
-            if let Some(current_value_t) = current_value.get_mut::<T>() {
-                *current_value_t = value;
-                true
-            } else {
-                false
+    //pads every dynamic uniform with default values out to at least count instances, and to the
+    //length of the longest one, so they all share offsets. Returns true if any of them grew
+    pub fn ensure_instances(&mut self, count: usize) -> bool {
+        let count = self.dynamic_uniforms.values().map(Vec::len).fold(count, usize::max);
+
+        let mut grown = false;
+        for (name, instances) in self.dynamic_uniforms.iter_mut() {
+            if instances.len() >= count { continue }
+
+            if let Some((_, _, default_value)) = self.uniforms.iter()
+                .find(|(uniform_name, _, _)| uniform_name == name)
+            {
+                instances.resize(count, default_value.clone());
+                grown = true;
             }
-        } else {
-            false
         }
+
+        grown
+    }
+
+    pub fn uniform<T: 'static>(mut self, name: &str, value: T) -> Result<Self, SimpleError> {
+        self.set_uniform(name, value)?;
+        Ok(self)
+    }
+
+    //setting a dynamic uniform directly writes instance 0
+    pub fn set_uniform<T: 'static>(&mut self, name: &str, value: T) -> Result<(), SimpleError> {
+        if self.dynamic_uniforms.contains_key(name) {
+            return self.set_uniform_instance(name, 0, value);
+        }
+
+        let (_, (..), current_value) = self.uniforms.iter_mut()
+            .find(|(uniform_name, _, _)| uniform_name == name)
+            .ok_or_else(|| Self::missing_uniform(name))?;
+
+        Self::write_value(name, current_value, value)
+    }
+
+    //grows the instance list with default values if the instance hasn't been written yet
+    pub fn set_uniform_instance<T: 'static>(&mut self, name: &str, instance: usize, value: T) -> Result<(), SimpleError> {
+        let (_, _, default_value) = self.uniforms.iter()
+            .find(|(uniform_name, _, _)| uniform_name == name)
+            .ok_or_else(|| Self::missing_uniform(name))?;
+
+        let instances = self.dynamic_uniforms.get_mut(name)
+            .ok_or_else(|| SimpleError::new(format!("Uniform {} isn't dynamic, it only has one instance", name)))?;
+
+        if instance >= instances.len() {
+            instances.resize(instance + 1, default_value.clone());
+        }
+
+        Self::write_value(name, &mut instances[instance], value)
+    }
+
+    fn missing_uniform(name: &str) -> SimpleError {
+        SimpleError::new(format!("Material has no uniform named {}", name))
+    }
+
+    fn write_value<T: 'static>(name: &str, current_value: &mut MaterialValue, value: T) -> Result<(), SimpleError> {
+        let expected = current_value.type_name();
+
+        let current_value_t = current_value.get_mut::<T>()
+            .ok_or_else(|| SimpleError::new(format!(
+                "Uniform {} expects a {} but was given a {}", name, expected, std::any::type_name::<T>()
+            )))?;
+
+        *current_value_t = value;
+        Ok(())
     }
 
     pub fn get_uniform<T: 'static>(&self, name: &str) -> Option<&T> {
+        if self.dynamic_uniforms.contains_key(name) {
+            return self.get_uniform_instance(name, 0);
+        }
+
         if let Some((_, (..), current_value)) = self.uniforms.iter()
-            .find(|(uniform_name, _, _)| uniform_name == name) 
+            .find(|(uniform_name, _, _)| uniform_name == name)
         {
             if let Some(current_value_t) = current_value.get::<T>() {
                 Some(current_value_t)
@@ -48,4 +124,37 @@ impl Material {
             None
         }
     }
-}
\ No newline at end of file
+
+    pub fn get_uniform_instance<T: 'static>(&self, name: &str, instance: usize) -> Option<&T> {
+        self.dynamic_uniforms.get(name)?
+            .get(instance)?
+            .get::<T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material() -> Material {
+        Material::new(vec![(String::from("brightness"), (0, 0), MaterialValue::Float(0f32))])
+    }
+
+    #[test]
+    fn missing_uniforms_are_named() {
+        let error = material().set_uniform("contrast", 1f32).unwrap_err();
+        assert_eq!(error.as_str(), "Material has no uniform named contrast");
+    }
+
+    #[test]
+    fn mismatched_types_name_both_types() {
+        let error = material().set_uniform("brightness", 1u32).unwrap_err();
+        assert_eq!(error.as_str(), "Uniform brightness expects a f32 but was given a u32");
+    }
+
+    #[test]
+    fn builder_reports_failed_updates() {
+        assert!(material().uniform("contrast", 1f32).is_err());
+        assert_eq!(material().uniform("brightness", 0.5f32).unwrap().get_uniform::<f32>("brightness"), Some(&0.5f32));
+    }
+}
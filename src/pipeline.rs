@@ -1,10 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, num::NonZeroU32};
 
 use naga::{ResourceBinding, Module, Handle, GlobalVariable, Expression};
 use simple_error::SimpleError;
 use wgpu::VertexBufferLayout;
 
-use crate::{shader_types::{create_binding_type, create_uniform_storage}, material::Material};
+use crate::{error::Error, shader_types::{create_binding_type, create_uniform_storage, binding_array_count, MaterialValue}, material::Material};
 
 #[derive(Clone, Debug)]
 pub struct Uniform {
@@ -12,15 +12,18 @@ pub struct Uniform {
     pub binding_type: wgpu::BindingType,
     pub visibility: wgpu::ShaderStages,
     pub naga_type: naga::TypeInner,
+    //how many resources a binding array holds, None for everything else
+    pub count: Option<NonZeroU32>,
 }
 
 impl<'a> Uniform {
-    fn new(binding: ResourceBinding, binding_type: wgpu::BindingType, visibility: wgpu::ShaderStages, naga_type: naga::TypeInner) -> Self {
+    fn new(binding: ResourceBinding, binding_type: wgpu::BindingType, visibility: wgpu::ShaderStages, naga_type: naga::TypeInner, count: Option<NonZeroU32>) -> Self {
         Self {
             binding,
             binding_type,
             visibility,
-            naga_type
+            naga_type,
+            count
         }
     }
 }
@@ -35,14 +38,16 @@ pub struct Pipeline {
     uniforms: HashMap<String, Uniform>,
     vs_entry_point: String,
     fs_entry_point: String,
-    vertex_buffer_layout: Option<wgpu::VertexBufferLayout<'static>>
+    vertex_buffer_layout: Option<wgpu::VertexBufferLayout<'static>>,
+    push_constant_range: Option<wgpu::PushConstantRange>,
+    depth_test: bool,
+    depth_write: bool,
 }
 
 impl Pipeline {
-    pub fn load<T: Vertex>(shader: &str) -> Result<Self, SimpleError> {
-        let shader_cource = String::from(shader);
-
-        let shader_module = naga::front::wgsl::parse_str(&shader_cource).expect("Failed to load shader!");
+    pub fn load<T: Vertex>(shader: &str) -> Result<Self, Error> {
+        let shader_module = naga::front::wgsl::parse_str(shader)
+            .map_err(|e| Error::ShaderParse(e.emit_to_string(shader)))?;
 
         let vs_entry_point = shader_module.entry_points.iter()
             .find(|entry_point| entry_point.stage == naga::ShaderStage::Vertex)
@@ -61,6 +66,7 @@ impl Pipeline {
             .clone();
 
         let mut uniforms = Self::parse_shader_uniforms(&shader_module)?;
+        let push_constant_range = Self::parse_push_constant_range(&shader_module)?;
         
         let vertex_buffer_layout = Some(T::desc());
 
@@ -71,10 +77,32 @@ impl Pipeline {
             uniforms,
             vs_entry_point,
             fs_entry_point,
-            vertex_buffer_layout
+            vertex_buffer_layout,
+            push_constant_range,
+            depth_test: true,
+            depth_write: true,
         })
     }
 
+    //stores the uniform in its own buffer with one value per instance, selected with a
+    //dynamic offset at draw time so many objects can share a single bind group
+    pub fn dynamic_uniform(mut self, name: &str) -> Result<Self, SimpleError> {
+        let uniform = self.uniforms.get_mut(name)
+            .ok_or(SimpleError::new(format!("Could not find uniform {} in shader", name)))?;
+
+        match &mut uniform.binding_type {
+            wgpu::BindingType::Buffer { has_dynamic_offset, .. } => *has_dynamic_offset = true,
+            _ => return Err(SimpleError::new(format!("Uniform {} is not a buffer and can't use a dynamic offset", name)))
+        }
+
+        Ok(self)
+    }
+
+    pub fn is_dynamic(&self, name: &str) -> bool {
+        matches!(self.uniforms.get(name).map(|uniform| &uniform.binding_type), 
+            Some(wgpu::BindingType::Buffer { has_dynamic_offset: true, .. }))
+    }
+
     pub fn bind_groups(&self) -> Vec<Vec<&Uniform>> {
         let mut groups: HashMap<u32, Vec<&Uniform>> = HashMap::new();
         
@@ -91,20 +119,86 @@ impl Pipeline {
         let uniforms = self.uniforms.iter()
             .map(|(name, uniform)| {
                 let binding = (uniform.binding.group, uniform.binding.binding);
-                let uniform_storage = create_uniform_storage(&uniform.naga_type)
-                    .expect(&format!("Failed to create storage for uniform: {}", name)); 
+                let uniform_storage = match (uniform.binding_type, uniform.count) {
+                    (wgpu::BindingType::Texture { .. }, Some(count)) => 
+                        MaterialValue::TextureArray(vec![Default::default(); count.get() as usize]),
+                    (wgpu::BindingType::Sampler(_), Some(count)) => 
+                        MaterialValue::SamplerArray(vec![Default::default(); count.get() as usize]),
+                    (wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { .. }, .. }, None) => 
+                        MaterialValue::Storage(Vec::new()),
+                    _ => create_uniform_storage(&uniform.naga_type)
+                        .expect(&format!("Failed to create storage for uniform: {}", name))
+                };
 
                 (name.clone(), binding, uniform_storage)
             })
             .collect::<Vec<_>>();
 
-        Material::new(uniforms)
+        let dynamic_uniforms = self.uniforms.keys()
+            .filter(|name| self.is_dynamic(name))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        Material::new(uniforms).dynamic_uniforms(&dynamic_uniforms)
     }
 
     pub fn shader(&self) -> &str { &self.shader_source }
     pub fn vs_entry_point(&self) -> &str { &self.vs_entry_point }
     pub fn fs_entry_point(&self) -> &str { &self.fs_entry_point }
     pub fn buffer_layouts(&self) -> &[VertexBufferLayout] { self.vertex_buffer_layout.as_slice() }
+    pub fn push_constant_ranges(&self) -> &[wgpu::PushConstantRange] { self.push_constant_range.as_slice() }
+    pub fn depth_tested(&self) -> bool { self.depth_test }
+    pub fn depth_written(&self) -> bool { self.depth_write }
+    pub fn uses_binding_arrays(&self) -> bool { self.uniforms.values().any(|uniform| uniform.count.is_some()) }
+
+    //without depth testing everything drawn with this pipeline lands on top of what's already
+    //been drawn, so overlays are ordered by their RenderStage instead of their depth. Turning it off
+    //also stops depth writes so later stages aren't hidden behind the overlay, call depth_write after to keep them
+    pub fn depth_test(mut self, depth_test: bool) -> Self {
+        self.depth_test = depth_test;
+        if !depth_test { self.depth_write = false; }
+        self
+    }
+
+    //transparent things usually want to test against depth without hiding what's behind them
+    pub fn depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write; self
+    }
+
+    //wgsl only allows one push constant variable, it always starts at offset 0
+    fn parse_push_constant_range(shader_module: &Module) -> Result<Option<wgpu::PushConstantRange>, SimpleError> {
+        let push_constant = shader_module.global_variables.iter()
+            .find(|(_, variable)| variable.space == naga::AddressSpace::PushConstant);
+
+        let (handle, variable) = match push_constant {
+            Some(push_constant) => push_constant,
+            None => return Ok(None)
+        };
+
+        let naga_type = &shader_module.types.get_handle(variable.ty)
+            .ok()
+            .ok_or("Can't find type definition!!!")?
+            .inner;
+
+        let size = match naga_type {
+            naga::TypeInner::Scalar { width, .. } => *width as u32,
+            naga::TypeInner::Vector { size, width, .. } => *size as u32 * *width as u32,
+            //columns are laid out like vectors, so a three row column is padded out to four
+            naga::TypeInner::Matrix { columns, rows, width } => {
+                let column_rows = if *rows == naga::VectorSize::Tri { 4 } else { *rows as u32 };
+                *columns as u32 * column_rows * *width as u32
+            },
+            naga::TypeInner::Struct { span, .. } => *span,
+            _ => return Err(SimpleError::new("Push constants must be a scalar, vector, matrix or struct"))
+        };
+
+        let stages = Self::get_variable_visibilities(shader_module)
+            .get(&handle)
+            .copied()
+            .unwrap_or(wgpu::ShaderStages::VERTEX_FRAGMENT);
+
+        Ok(Some(wgpu::PushConstantRange { stages, range: 0..size }))
+    }
 
     fn parse_shader_uniforms(shader_module: &Module) -> Result<HashMap<String, Uniform>, SimpleError> {
         let mut uniforms: HashMap<String, Uniform> = HashMap::new();
@@ -133,10 +227,17 @@ impl Pipeline {
                 .inner
                 .clone();
 
-            let binding_type = create_binding_type(&naga_type)
-                .ok_or("Failed to translate naga type to binding type")?;
+            //this is also where arrays outside of storage buffers get rejected
+            let binding_type = create_binding_type(&naga_type, variable.space, naga_types)
+                .ok_or(SimpleError::new(format!("Failed to translate the type of {} to a binding type", name)))?;
+
+            let count = binding_array_count(&naga_type, &shader_module.constants)?;
+            //materials only know how to fill binding arrays with textures and samplers
+            if count.is_some() && matches!(binding_type, wgpu::BindingType::Buffer { .. }) {
+                return Err(SimpleError::new(format!("Binding array {} must hold textures or samplers", name)));
+            }
             
-            let uniform = Uniform::new(binding.clone(), binding_type, visibility, naga_type);
+            let uniform = Uniform::new(binding.clone(), binding_type, visibility, naga_type, count);
             let should_be_none = uniforms.insert(name.clone(), uniform);
             if should_be_none.is_some() { 
                 panic!("Defining same uniform name twice!");
@@ -208,4 +309,94 @@ impl Vertex for i32 {
             attributes: &ATTRIBS,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAT3_PUSH_CONSTANT_SHADER: &str = "
+        var<push_constant> transform: mat3x3<f32>;
+
+        @vertex
+        fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(transform * position, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(1.0);
+        }
+    ";
+
+    #[test]
+    fn matrix_push_constants_pad_their_columns() {
+        let pipeline = Pipeline::load::<crate::graphics::Vertex>(MAT3_PUSH_CONSTANT_SHADER).unwrap();
+
+        assert_eq!(pipeline.push_constant_ranges()[0].range, 0..48);
+    }
+
+    #[test]
+    fn disabling_depth_test_disables_depth_writes() {
+        let pipeline = Pipeline::load::<crate::graphics::Vertex>(MAT3_PUSH_CONSTANT_SHADER).unwrap();
+        assert!(pipeline.depth_tested() && pipeline.depth_written());
+
+        let overlay = pipeline.clone().depth_test(false);
+        assert!(!overlay.depth_tested() && !overlay.depth_written());
+
+        let writes_depth = pipeline.depth_test(false).depth_write(true);
+        assert!(!writes_depth.depth_tested() && writes_depth.depth_written());
+    }
+
+    #[test]
+    fn read_only_storage_buffers_get_storage_values() {
+        let pipeline = Pipeline::load::<crate::graphics::Vertex>("
+            @group(0) @binding(0) var<storage, read> heights: array<f32>;
+
+            @vertex
+            fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(position.xy, heights[0], 1.0);
+            }
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return vec4<f32>(1.0);
+            }
+        ").unwrap();
+
+        assert!(matches!(pipeline.uniforms["heights"].binding_type, wgpu::BindingType::Buffer { 
+            ty: wgpu::BufferBindingType::Storage { read_only: true }, .. 
+        }));
+
+        let material = pipeline.new_material();
+        let (_, _, value) = material.uniforms().iter().find(|(name, ..)| name == "heights").unwrap();
+        assert!(matches!(value, MaterialValue::Storage(bytes) if bytes.is_empty()));
+    }
+
+    #[test]
+    fn binding_arrays_bind_their_base_type() {
+        let pipeline = Pipeline::load::<crate::graphics::Vertex>("
+            @group(0) @binding(0) var layers: binding_array<texture_2d<f32>, 4>;
+            @group(0) @binding(1) var layer_sampler: sampler;
+
+            @vertex
+            fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+                return vec4<f32>(position, 1.0);
+            }
+
+            @fragment
+            fn fs_main() -> @location(0) vec4<f32> {
+                return textureSample(layers[0], layer_sampler, vec2<f32>(0.5));
+            }
+        ").unwrap();
+
+        let layers = &pipeline.uniforms["layers"];
+        assert!(matches!(layers.binding_type, wgpu::BindingType::Texture { .. }));
+        assert_eq!(layers.count, NonZeroU32::new(4));
+        assert!(pipeline.uses_binding_arrays());
+
+        let material = pipeline.new_material();
+        let (_, _, value) = material.uniforms().iter().find(|(name, ..)| name == "layers").unwrap();
+        assert!(matches!(value, MaterialValue::TextureArray(textures) if textures.len() == 4));
+    }
+}
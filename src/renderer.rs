@@ -1,4 +1,4 @@
-use std::{borrow::Cow, collections::HashMap, num::NonZeroU64, ops::Deref, time::Instant};
+use std::{borrow::Cow, collections::HashMap, num::NonZeroU64, ops::Deref, time::{Instant, Duration}};
 use core::fmt::Debug;
 
 use cgmath::{Matrix4, SquareMatrix};
@@ -9,7 +9,7 @@ use uuid::Uuid;
 use wgpu::{Instance, Surface, Adapter, Device, Queue, SurfaceConfiguration, Buffer, util::DeviceExt, RenderPipeline, BindGroup, BindGroupLayout, CommandBuffer, SurfaceTexture, SurfaceError};
 use winit::{dpi::PhysicalSize, window::Window};
 
-use crate::{graphics::{Rectangle, Vertex}, pipeline::Pipeline, material::Material, shader_types::{MaterialValue, Matrix}, camera::Camera};
+use crate::{error::Error, immediate::ImmediateDraw, graphics::{Rectangle, Vertex}, pipeline::Pipeline, material::Material, shader_types::{MaterialValue, Matrix}, camera::Camera};
 
 pub struct Renderer {
     textures: HashMap<Uuid, wgpu::Texture>,
@@ -18,6 +18,13 @@ pub struct Renderer {
     
     materials: HashMap<Uuid, MaterialInfo>,
 
+    //vertex and index buffers kept between frames, keyed by stage, material and uniform instance
+    geometry: HashMap<(u32, MaterialHandle, u32), GeometryBuffers>,
+
+    //shapes from the draw_ functions, drawn over everything on the next render
+    immediate: ImmediateDraw,
+    immediate_material: Option<MaterialHandle>,
+
     graphics: Graphics
 }
 
@@ -28,23 +35,35 @@ pub type PipelineHandle = Uuid;
 pub type MaterialHandle = Uuid;
 
 impl Renderer {
-    pub fn new(window: &Window) -> Self {
-        let graphics = pollster::block_on(Graphics::new(window));
+    pub fn new(window: &Window) -> Result<Self, Error> {
+        let graphics = pollster::block_on(Graphics::new(window))?;
+        Ok(Self::with_graphics(graphics))
+    }
 
+    //renders into an offscreen texture instead of a window, frames can be read back with capture_frame_image
+    pub fn headless(width: u32, height: u32) -> Result<Self, Error> {
+        let graphics = pollster::block_on(Graphics::new_headless(width, height))?;
+        Ok(Self::with_graphics(graphics))
+    }
+
+    fn with_graphics(graphics: Graphics) -> Self {
         Self {
             textures: HashMap::new(),
             samplers: HashMap::new(),
             pipelines: HashMap::new(),
             materials: HashMap::new(),
+            geometry: HashMap::new(),
+            immediate: ImmediateDraw::default(),
+            immediate_material: None,
             graphics
         }
     }
 
     #[inline]
-    fn all_materials<'a>(query_vec: &[(&'a MaterialHandle, &Rectangle, &RenderStage)]) -> Vec<&'a MaterialHandle> {
+    fn all_materials<'a>(query_vec: &[RenderQueryItem<'a>]) -> Vec<&'a MaterialHandle> {
         let mut all_materials = Vec::new();
         
-        for (material, _, _) in query_vec {
+        for (material, _, _, _) in query_vec {
             all_materials.push(*material);
         }
 
@@ -54,9 +73,23 @@ impl Renderer {
         all_materials
     }
 
+    //how many instances of its dynamic uniforms each material is drawn with this frame
+    fn instance_counts(query_vec: &[RenderQueryItem]) -> HashMap<MaterialHandle, usize> {
+        let mut instance_counts = HashMap::new();
+
+        for (material, _, _, instance) in query_vec {
+            let count = instance.map(|instance| instance.index as usize + 1).unwrap_or(1);
+            instance_counts.entry(**material)
+                .and_modify(|max_count: &mut usize| *max_count = (*max_count).max(count))
+                .or_insert(count);
+        }
+
+        instance_counts
+    }
+
     #[inline]
-    fn primitive_by_stage_by_material<'a>(query_vec: &[(&MaterialHandle, &'a Rectangle, &RenderStage)]) 
-        -> Option<Vec<HashMap<MaterialHandle, Vec<&'a Rectangle>>>>
+    fn primitive_by_stage_by_material<'a>(query_vec: &[RenderQueryItem<'a>]) 
+        -> Option<Vec<HashMap<(MaterialHandle, u32), Vec<&'a Rectangle>>>>
     {
         let mut rects_by_stage_by_material = Vec::new();
         rects_by_stage_by_material.push(HashMap::new());
@@ -65,8 +98,8 @@ impl Renderer {
         if let Some(first) = query_vec.first()
             { first.2.order } else { return None };
 
-        //order by stage and then by material
-        for (material, rect, stage) in query_vec {
+        //order by stage and then by material and uniform instance
+        for (material, rect, stage, instance) in query_vec {
             if stage.order != current_stage {
                 current_stage = stage.order;
                 rects_by_stage_by_material.push(HashMap::new());
@@ -75,7 +108,7 @@ impl Renderer {
             rects_by_stage_by_material
                 .last_mut()
                 .unwrap()
-                .entry(**material)
+                .entry((**material, instance.map(|instance| instance.index).unwrap_or(0)))
                 .and_modify(|v: &mut Vec<&Rectangle>| v.push(rect))
                 .or_insert(vec![rect]);
         }
@@ -86,12 +119,21 @@ impl Renderer {
     pub fn render(&mut self, world: &World) -> Result<(), wgpu::SurfaceError> {
         let start = Instant::now();
         
-        let mut query = <(&MaterialHandle, &Rectangle, &RenderStage)>::query();
+        let mut query = <(&MaterialHandle, &Rectangle, &RenderStage, Option<&UniformInstance>)>::query();
+
+        //immediate shapes go in the last stage so they draw over everything else
+        let immediate_shapes = self.immediate.take();
+        let immediate_stage = RenderStage { order: u32::MAX };
+        let immediate_material = self.immediate_material;
 
         let mut query_vec = query.iter(world).collect::<Vec<_>>();
-        query_vec.sort_by(|(_, _, stage_1), (_, _, stage_2)| stage_1.order.cmp(&stage_2.order));
+        if let Some(material) = immediate_material.as_ref() {
+            query_vec.extend(immediate_shapes.iter().map(|shape| (material, shape, &immediate_stage, None)));
+        }
+        query_vec.sort_by(|(_, _, stage_1, _), (_, _, stage_2, _)| stage_1.order.cmp(&stage_2.order));
         
         let all_materials = Self::all_materials(&query_vec);
+        let instance_counts = Self::instance_counts(&query_vec);
         
         //grab the camera from the scene
         let mut camera_query = <&Camera>::query();
@@ -102,33 +144,46 @@ impl Renderer {
         for material in all_materials.iter() {
             //try and update the view_proj matrix, may fail, but that is fine
             let matrix = Matrix::from(view_proj_matrix);
-            self.update_material(**material, "view_proj", matrix);
+            self.update_material(**material, "view_proj", matrix).ok();
             
             let material_info = self.materials.get_mut(material).unwrap();
+
+            //instances that were never written draw with the defaults instead of offsetting past the buffer
+            if material_info.cpu_storage.ensure_instances(instance_counts[*material]) {
+                material_info.dirty = true;
+            }
             
             if material_info.dirty || material_info.bind_groups.is_none() {
-                let updated_bind_groups = self.create_bind_groups(material).unwrap();
+                let bind_groups = self.create_bind_groups(material);
 
+                //materials that can't be bound are skipped, the error is only reported when it changes
                 let material_info = self.materials.get_mut(material).unwrap();
-                material_info.bind_groups = Some(updated_bind_groups);
+                match bind_groups {
+                    Ok((updated_bind_groups, dynamic_strides)) => {
+                        material_info.bind_groups = Some(updated_bind_groups);
+                        material_info.dynamic_strides = dynamic_strides;
+                        material_info.bind_error = None;
+                    }
+                    Err(e) => {
+                        if material_info.bind_error.as_ref() != Some(&e.to_string()) {
+                            eprintln!("Skipping material that can't be bound: {}", e);
+                        }
+                        material_info.bind_groups = None;
+                        material_info.bind_error = Some(e.to_string());
+                    }
+                }
                 material_info.dirty = false;
             }
         }
 
-        let rects_by_stage_by_material = Self::primitive_by_stage_by_material(&query_vec);
-        if rects_by_stage_by_material.is_none() { return Ok(()) }
-        let rects_by_stage_by_material = rects_by_stage_by_material.unwrap();
-
-        self.graphics.begin_render([0f32, 0f32, 0f32])?;
-        for rects_by_material in rects_by_stage_by_material.iter() {
-            let mut render_tasks = Vec::new();
-            for (material, rectangles) in rects_by_material.iter() {
-
-                let material_info = match self.materials.get(material) {
-                    Some(material) => material,
-                    None => continue,
-                };
+        //an empty scene still clears to the clear color
+        let rects_by_stage_by_material = Self::primitive_by_stage_by_material(&query_vec)
+            .unwrap_or_default();
 
+        //write into last frame's buffers where they fit, anything not drawn this frame is dropped
+        let mut geometry = HashMap::new();
+        for (stage, rects_by_material) in rects_by_stage_by_material.iter().enumerate() {
+            for ((material, instance), rectangles) in rects_by_material.iter() {
                 let vertices = rectangles
                     .iter()
                     .flat_map(|rect| rect.vertices)
@@ -141,18 +196,46 @@ impl Renderer {
                         .map(move |e| *e + (i * 4) as u32))
                     .collect::<Vec<_>>();
 
-                let vertex_buffer = self.graphics.create_vertex_buffer(&vertices);
-                let index_buffer = self.graphics.create_index_buffer(&indices);
-                let num_indices = indices.len() as u32;
+                let key = (stage as u32, *material, *instance);
+                let previous = self.geometry.remove(&key);
+                geometry.insert(key, self.graphics.write_geometry(previous, &vertices, &indices));
+            }
+        }
+        self.geometry = geometry;
 
-                let pipeline = &self.pipelines.get(&material_info.pipeline).as_ref().unwrap().1.pipeline;
+        //the surface couldn't be drawn to this frame, it's been reconfigured if it needed to be
+        if !self.graphics.begin_render()? {
+            return Ok(())
+        }
+        for (stage, rects_by_material) in rects_by_stage_by_material.iter().enumerate() {
+            let mut render_tasks = Vec::new();
+            for (material, instance) in rects_by_material.keys() {
+
+                let material_info = match self.materials.get(material) {
+                    Some(material) if material.bind_groups.is_some() => material,
+                    _ => continue,
+                };
+
+                let geometry = &self.geometry[&(stage as u32, *material, *instance)];
+
+                let (pipeline, loaded_pipeline) = self.pipelines.get(&material_info.pipeline).unwrap();
+                let push_constant_stages = pipeline.push_constant_ranges().first()
+                    .map(|range| range.stages)
+                    .unwrap_or(wgpu::ShaderStages::NONE);
+
+                let dynamic_offsets = material_info.dynamic_strides.iter()
+                    .map(|strides| strides.iter().map(|stride| stride * instance).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
 
                 render_tasks.push(RenderWork {
-                    pipeline, 
+                    pipeline: &loaded_pipeline.pipeline, 
                     bind_groups: material_info.bind_groups.as_ref().unwrap(), 
-                    vertex_buffer, 
-                    index_buffer, 
-                    num_indices
+                    dynamic_offsets,
+                    push_constants: &material_info.push_constants,
+                    push_constant_stages,
+                    vertex_buffer: &geometry.vertex_buffer, 
+                    index_buffer: &geometry.index_buffer, 
+                    num_indices: geometry.num_indices
                 });
             }
 
@@ -170,45 +253,130 @@ impl Renderer {
 
     }
 
+    //blocks until the gpu has finished everything submitted so far, this stalls the
+    //pipeline so it's meant for tests and readback, not for every frame
+    pub fn submit_and_wait(&mut self) {
+        self.graphics.submit_and_wait();
+    }
+
+    //saves the next rendered frame to a png before it's presented, check take_capture_result to see if it worked
+    pub fn capture_frame(&mut self, path: &str) {
+        self.graphics.pending_capture = Some(CaptureTarget::File(path.to_string()));
+    }
+
+    //reads the next rendered frame back into memory, collect it with take_capture_result after rendering
+    pub fn capture_frame_image(&mut self) {
+        self.graphics.pending_capture = Some(CaptureTarget::Memory);
+    }
+
+    //the pixels from the last captured frame or why capturing it failed, None until one has been rendered
+    pub fn take_capture_result(&mut self) -> Option<Result<image::RgbaImage, SimpleError>> {
+        self.graphics.capture_result.take()
+    }
+
+    //times each render pass on the gpu, returns false if the adapter can't write timestamps.
+    //reading the timings back waits on the gpu every frame, so only leave this on while profiling
+    pub fn enable_gpu_timings(&mut self, enabled: bool) -> bool {
+        if !enabled {
+            self.graphics.gpu_timer = None;
+            self.graphics.gpu_timings = None;
+            return true
+        }
+
+        if !self.graphics.device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return false
+        }
+
+        if self.graphics.gpu_timer.is_none() {
+            self.graphics.gpu_timer = Some(GpuTimer::new(&self.graphics.device));
+        }
+        true
+    }
+
+    //timings from the last frame rendered while gpu timings were enabled
+    pub fn gpu_timings(&self) -> Option<&GpuTimings> {
+        self.graphics.gpu_timings.as_ref()
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: [f32; 3]) {
+        self.graphics.clear_color = clear_color;
+    }
+
+    //the widest or tallest texture the device can create
+    pub fn max_texture_dimension(&self) -> u32 {
+        self.graphics.device.limits().max_texture_dimension_2d
+    }
+
     pub fn find_display(&mut self) {
-        self.graphics.resize(self.graphics.size());
+        self.graphics.reconfigure();
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         self.graphics.resize(new_size);
     }
 
-    pub fn load_texture(&mut self, file: &str) -> Result<TextureHandle, SimpleError> {
+    pub fn load_texture(&mut self, file: &str) -> Result<TextureHandle, Error> {
+        self.load_texture_with_mips(file, false)
+    }
+
+    //mipmapped textures stop shimmering when drawn smaller than their size, pair them with create_mipmap_sampler
+    pub fn load_mipmapped_texture(&mut self, file: &str) -> Result<TextureHandle, Error> {
+        self.load_texture_with_mips(file, true)
+    }
+
+    fn load_texture_with_mips(&mut self, file: &str, generate_mips: bool) -> Result<TextureHandle, Error> {
         let uuid = Uuid::new_v4();
 
-        let diffuse_bytes = std::fs::read(file).expect("Can't read texture file");
-        let diffuse_image = image::load_from_memory(&diffuse_bytes).unwrap();
+        let diffuse_bytes = std::fs::read(file)
+            .map_err(|source| Error::Io { path: file.to_string(), source })?;
+        let diffuse_image = image::load_from_memory(&diffuse_bytes)?;
         let diffuse_rgba = diffuse_image.to_rgba8();
 
-        self.textures.insert(uuid, self.graphics.create_texture(diffuse_rgba)?);
+        self.textures.insert(uuid, self.graphics.create_texture(diffuse_rgba, generate_mips)?);
         Ok(uuid)
     }
 
     pub fn create_texture<P, S>(&mut self, image: ImageBuffer<P, S>) -> Result<TextureHandle, SimpleError> 
     where 
-        P: image::Pixel<Subpixel = u8>,
+        P: image::Pixel<Subpixel = u8> + 'static,
+        S: Deref<Target = [<P as image::Pixel>::Subpixel]>,
+    {
+        let uuid = Uuid::new_v4();
+        self.textures.insert(uuid, self.graphics.create_texture(image, false)?);
+        Ok(uuid)
+    }
+
+    pub fn create_mipmapped_texture<P, S>(&mut self, image: ImageBuffer<P, S>) -> Result<TextureHandle, SimpleError> 
+    where 
+        P: image::Pixel<Subpixel = u8> + 'static,
         S: Deref<Target = [<P as image::Pixel>::Subpixel]>,
     {
         let uuid = Uuid::new_v4();
-        self.textures.insert(uuid, self.graphics.create_texture(image)?);
+        self.textures.insert(uuid, self.graphics.create_texture(image, true)?);
         Ok(uuid)
     }
 
     pub fn create_sampler(&mut self, ) -> SamplerHandle {
+        self.create_sampler_with(SamplerConfig::default())
+    }
+
+    //blends between mip levels, use with textures that have a mip chain
+    pub fn create_mipmap_sampler(&mut self) -> SamplerHandle {
+        self.create_sampler_with(SamplerConfig::mipmapped())
+    }
+
+    pub fn create_sampler_with(&mut self, config: SamplerConfig) -> SamplerHandle {
         let uuid = Uuid::new_v4();
-        self.samplers.insert(uuid, self.graphics.create_sampler());
+        self.samplers.insert(uuid, self.graphics.create_sampler(&config));
         uuid
     }
 
-    pub fn create_pipeline(&mut self, pipeline: Pipeline) -> PipelineHandle {
+    pub fn create_pipeline(&mut self, pipeline: Pipeline) -> Result<PipelineHandle, Error> {
+        let loaded_pipeline = self.graphics.load_pipeline(pipeline.clone())?;
+
         let uuid = Uuid::new_v4();
-        self.pipelines.insert(uuid, (pipeline.clone(), self.graphics.load_pipeline(pipeline)));
-        uuid
+        self.pipelines.insert(uuid, (pipeline, loaded_pipeline));
+        Ok(uuid)
     }
 
     pub fn create_material(&mut self, pipeline_handle: PipelineHandle) -> Result<MaterialHandle, SimpleError> {
@@ -222,6 +390,9 @@ impl Renderer {
             pipeline: pipeline_handle,
             cpu_storage,
             bind_groups: None,
+            dynamic_strides: Vec::new(),
+            push_constants: Vec::new(),
+            bind_error: None,
             dirty: true,
         };
 
@@ -229,19 +400,95 @@ impl Renderer {
         Ok(uuid)
     }
 
-    pub fn update_material<T>(&mut self, material_handle: MaterialHandle, name: &str, value: T) -> bool 
+    pub fn update_material<T>(&mut self, material_handle: MaterialHandle, name: &str, value: T) -> Result<(), SimpleError> 
         where T: 'static + Debug
     {
-        if let Some(material) = self.materials.get_mut(&material_handle) {
-            if material.cpu_storage.set_uniform(name, value) {
-                material.dirty = true;
-                return true;
-            }   
+        let material = self.materials.get_mut(&material_handle)
+            .ok_or(SimpleError::new("Could not find material to update"))?;
+
+        material.cpu_storage.set_uniform(name, value)?;
+        material.dirty = true;
+        Ok(())
+    }
+
+    //lines, rects and circles drawn in world space on top of the scene for the next frame only,
+    //call these every frame the shapes should stay on screen
+    pub fn draw_line(&mut self, a: (f32, f32), b: (f32, f32), width: f32, color: [f32; 3]) -> Result<(), Error> {
+        self.load_immediate_material()?;
+        self.immediate.line(a, b, width, color);
+        Ok(())
+    }
+
+    pub fn draw_rect(&mut self, position: (f32, f32), size: (f32, f32), color: [f32; 3]) -> Result<(), Error> {
+        self.load_immediate_material()?;
+        self.immediate.rect(position, size, color);
+        Ok(())
+    }
+
+    pub fn draw_circle(&mut self, center: (f32, f32), radius: f32, color: [f32; 3]) -> Result<(), Error> {
+        self.load_immediate_material()?;
+        self.immediate.circle(center, radius, color);
+        Ok(())
+    }
+
+    //rect.wgsl draws in clip space and cuts each quad away towards its corners, so immediate
+    //shapes use the plain vertex colored shader with the camera applied instead
+    fn load_immediate_material(&mut self) -> Result<(), Error> {
+        if self.immediate_material.is_some() { return Ok(()) }
+
+        let pipeline = Pipeline::load::<Vertex>(include_str!("untextured.wgsl"))?
+            .depth_test(false);
+        let pipeline_handle = self.create_pipeline(pipeline)?;
+
+        self.immediate_material = Some(self.create_material(pipeline_handle)?);
+        Ok(())
+    }
+
+    //push constants are set once per draw of the material, they have to fill the whole range the shader declares
+    pub fn set_push_constants(&mut self, material_handle: MaterialHandle, bytes: &[u8]) -> Result<(), SimpleError> {
+        let material = self.materials.get_mut(&material_handle)
+            .ok_or(SimpleError::new("Could not find material to set push constants on"))?;
+
+        let range = self.pipelines.get(&material.pipeline)
+            .and_then(|(pipeline, _)| pipeline.push_constant_ranges().first().cloned())
+            .ok_or(SimpleError::new("Material's shader doesn't declare any push constants"))?;
+
+        if bytes.len() as u32 != range.range.end {
+            return Err(SimpleError::new(format!("Push constants must be {} bytes, got {}", range.range.end, bytes.len())));
+        }
+
+        material.push_constants = bytes.to_vec();
+        Ok(())
+    }
+
+    //checks everything the material binds has been assigned, reporting every problem at once
+    //so they can be fixed up front instead of failing in the middle of a frame
+    pub fn validate_material(&self, material_handle: MaterialHandle) -> Result<(), Vec<String>> {
+        let material = self.materials.get(&material_handle)
+            .ok_or_else(|| vec![String::from("Could not find material")])?;
+
+        let mut problems = Vec::new();
+        if !self.pipelines.contains_key(&material.pipeline) {
+            problems.push(String::from("Could not find pipeline for material"));
         }
-        false
+        problems.extend(unbound_uniforms(&material.cpu_storage, &self.textures, &self.samplers));
+
+        if problems.is_empty() { Ok(()) } else { Err(problems) }
+    }
+
+    //writes one instance of a dynamic uniform, entities pick it with a UniformInstance component
+    pub fn update_material_instance<T>(&mut self, material_handle: MaterialHandle, name: &str, instance: u32, value: T) -> Result<(), SimpleError> 
+        where T: 'static + Debug
+    {
+        let material = self.materials.get_mut(&material_handle)
+            .ok_or(SimpleError::new("Could not find material to update"))?;
+
+        material.cpu_storage.set_uniform_instance(name, instance as usize, value)?;
+        material.dirty = true;
+        Ok(())
     }
 
-    fn create_bind_groups(&self, material_handle: &Uuid) -> Result<Vec<wgpu::BindGroup>, SimpleError> {
+    fn create_bind_groups(&self, material_handle: &Uuid) -> Result<(Vec<wgpu::BindGroup>, Vec<Vec<u32>>), SimpleError> {
         let material_info = self.materials.get(material_handle).unwrap();
         
         let uniforms = material_info.cpu_storage.uniforms();
@@ -252,7 +499,13 @@ impl Renderer {
         
         let mut texture_views = HashMap::new();
         for (name, _, value) in uniforms.iter() {
-            if let MaterialValue::Texture(texture) = value {
+            let textures = match value {
+                MaterialValue::Texture(texture) => std::slice::from_ref(texture),
+                MaterialValue::TextureArray(textures) => textures.as_slice(),
+                _ => continue
+            };
+
+            for texture in textures {
                 let uuid = &texture.uuid
                     .ok_or(SimpleError::new(&format!("Could not find texture for material bound at: {}", name)))?;
                 let texture_view = self.textures.get(uuid)
@@ -264,18 +517,187 @@ impl Renderer {
         }
 
         //layouts, uniforms, textures, samplers
-        self.graphics.create_bind_groups(bind_group_layouts, uniforms, &texture_views, &self.samplers)
+        self.graphics.create_bind_groups(
+            bind_group_layouts, 
+            uniforms, 
+            material_info.cpu_storage.dynamic_instances(), 
+            &texture_views, 
+            &self.samplers
+        )
     } 
 }
 
+//every texture, sampler and buffer the material would fail to bind, the maps are the renderer's loaded resources
+fn unbound_uniforms<T, S>(material: &Material, textures: &HashMap<Uuid, T>, samplers: &HashMap<Uuid, S>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for (name, _, value) in material.uniforms() {
+        match value {
+            MaterialValue::Texture(texture) => match texture.uuid {
+                None => problems.push(format!("Material was never assigned texture at: {}", name)),
+                Some(uuid) if !textures.contains_key(&uuid) => 
+                    problems.push(format!("Cannot find texture assigned to material at: {}", name)),
+                _ => {}
+            },
+            MaterialValue::Sampler(sampler) => match sampler.uuid {
+                None => problems.push(format!("Material was never assigned sampler at: {}", name)),
+                Some(uuid) if !samplers.contains_key(&uuid) => 
+                    problems.push(format!("Cannot find sampler assigned to material at: {}", name)),
+                _ => {}
+            },
+            MaterialValue::TextureArray(array) => for (i, texture) in array.iter().enumerate() {
+                match texture.uuid {
+                    None => problems.push(format!("Material was never assigned texture at: {}[{}]", name, i)),
+                    Some(uuid) if !textures.contains_key(&uuid) => 
+                        problems.push(format!("Cannot find texture assigned to material at: {}[{}]", name, i)),
+                    _ => {}
+                }
+            },
+            MaterialValue::SamplerArray(array) => for (i, sampler) in array.iter().enumerate() {
+                match sampler.uuid {
+                    None => problems.push(format!("Material was never assigned sampler at: {}[{}]", name, i)),
+                    Some(uuid) if !samplers.contains_key(&uuid) => 
+                        problems.push(format!("Cannot find sampler assigned to material at: {}[{}]", name, i)),
+                    _ => {}
+                }
+            },
+            MaterialValue::Storage(bytes) if bytes.is_empty() => 
+                problems.push(format!("Storage buffer {} was never given any data", name)),
+            MaterialValue::Struct(_) => 
+                problems.push(format!("Struct uniform {} can't be bound", name)),
+            _ => {}
+        }
+    }
+
+    problems
+}
+
 pub struct RenderStage {
     pub order: u32,
 }
 
+//the default matches the sampler create_sampler has always made, use Repeat addressing for tiling textures
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerConfig {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+        }
+    }
+}
+
+impl SamplerConfig {
+    //linear minification and mip blending
+    pub fn mipmapped() -> Self {
+        Self {
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }
+    }
+
+    pub fn address_mode(mut self, address_mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = address_mode;
+        self.address_mode_v = address_mode;
+        self.address_mode_w = address_mode;
+        self
+    }
+}
+
+//gpu time spent in each render stage's pass, in stage order
+#[derive(Clone, Debug, Default)]
+pub struct GpuTimings {
+    pub passes: Vec<Duration>,
+}
+
+impl GpuTimings {
+    pub fn total(&self) -> Duration { self.passes.iter().sum() }
+}
+
+//passes past this many in a frame go untimed
+const MAX_TIMED_PASSES: u32 = 32;
+
+struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: Buffer,
+    read_buffer: Buffer,
+    //timestamps written so far this frame, two per pass
+    next_query: u32,
+}
+
+impl GpuTimer {
+    fn new(device: &Device) -> Self {
+        let query_count = MAX_TIMED_PASSES * 2;
+        let size = query_count as u64 * std::mem::size_of::<u64>() as u64;
+
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Timestamp Queries"),
+                ty: wgpu::QueryType::Timestamp,
+                count: query_count,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Resolve Buffer"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            read_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Read Buffer"),
+                size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            next_query: 0,
+        }
+    }
+
+    //index of the start timestamp for the next pass, if there's room left this frame
+    fn next_pass(&mut self) -> Option<u32> {
+        if self.next_query >= MAX_TIMED_PASSES * 2 {
+            return None
+        }
+
+        let query = self.next_query;
+        self.next_query += 2;
+        Some(query)
+    }
+}
+
+//selects which instance of a material's dynamic uniforms an entity is drawn with
+pub struct UniformInstance {
+    pub index: u32,
+}
+
+type RenderQueryItem<'a> = (&'a MaterialHandle, &'a Rectangle, &'a RenderStage, Option<&'a UniformInstance>);
+
 struct MaterialInfo {
     pipeline: PipelineHandle,
     cpu_storage: Material,
     bind_groups: Option<Vec<wgpu::BindGroup>>,
+    //stride of each dynamic binding, per bind group, in binding order
+    dynamic_strides: Vec<Vec<u32>>,
+    push_constants: Vec<u8>,
+    //why the bind groups couldn't be created last time they were tried
+    bind_error: Option<String>,
     dirty: bool
 }
 
@@ -284,32 +706,67 @@ struct LoadedPipeline {
     bind_group_layouts: Vec<(u32, BindGroupLayout)>,
 }
 
+struct GeometryBuffers {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+}
+
 pub struct RenderWork<'a> {
     pipeline: &'a RenderPipeline,
     bind_groups: &'a [BindGroup], 
-    vertex_buffer: Buffer, 
-    index_buffer: Buffer, 
+    dynamic_offsets: Vec<Vec<u32>>,
+    push_constants: &'a [u8],
+    push_constant_stages: wgpu::ShaderStages,
+    vertex_buffer: &'a Buffer, 
+    index_buffer: &'a Buffer, 
     num_indices: u32
 }
 
+enum CaptureTarget {
+    File(String),
+    Memory,
+}
+
 pub struct Graphics {
     _instance: Instance,
-    surface: Surface,
+    //headless graphics have no surface and draw into offscreen_texture instead
+    surface: Option<Surface>,
+    offscreen_texture: Option<wgpu::Texture>,
     _adapter: Adapter,
     device: Device,
     queue: Queue,
     config: SurfaceConfiguration,
     size: PhysicalSize<u32>,
     depth_texture: (wgpu::Texture, wgpu::TextureView, wgpu::Sampler),
+    clear_color: [f32; 3],
 
     current_surface_texture: Option<SurfaceTexture>,
     command_buffers: Vec<CommandBuffer>, 
+
+    pending_capture: Option<CaptureTarget>,
+    capture_result: Option<Result<image::RgbaImage, SimpleError>>,
+
+    gpu_timer: Option<GpuTimer>,
+    gpu_timings: Option<GpuTimings>,
 }
 
 impl Graphics {
-    fn begin_render(&mut self, clear_color: [f32; 3]) -> Result<(), SurfaceError>{
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    //returns false when the frame should be skipped, only running out of memory is an error
+    fn begin_render(&mut self) -> Result<bool, SurfaceError>{
+        let clear_color = self.clear_color;
+
+        self.current_surface_texture = match self.surface.as_ref().map(|surface| surface.get_current_texture()) {
+            Some(Ok(surface_texture)) => Some(surface_texture),
+            Some(Err(SurfaceError::Lost | SurfaceError::Outdated)) => {
+                self.reconfigure();
+                return Ok(false)
+            },
+            Some(Err(SurfaceError::Timeout)) => return Ok(false),
+            Some(Err(e)) => return Err(e),
+            None => None
+        };
+        let view = self.frame_texture().create_view(&wgpu::TextureViewDescriptor::default());
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
@@ -343,24 +800,35 @@ impl Graphics {
         }
 
         self.command_buffers.push(encoder.finish());
-        self.current_surface_texture.replace(output);
 
-        Ok(())
+        Ok(true)
+    }
+
+    fn frame_texture(&self) -> &wgpu::Texture {
+        self.current_surface_texture.as_ref()
+            .map(|surface_texture| &surface_texture.texture)
+            .or(self.offscreen_texture.as_ref())
+            .expect("Render must be called after starting to render")
     }
 
     fn render(&mut self, 
         work: Vec<RenderWork>,
     )  -> Result<(), wgpu::SurfaceError> {
         
-        let view = self.current_surface_texture.as_ref()
-            .expect("Render must be called after starting to render")
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let view = self.frame_texture().create_view(&wgpu::TextureViewDescriptor::default());
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
+        let timestamp = self.gpu_timer.as_mut().and_then(|timer| {
+            let query = timer.next_pass()?;
+            Some((&timer.query_set, query))
+        });
+        if let Some((query_set, query)) = timestamp {
+            encoder.write_timestamp(query_set, query);
+        }
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -386,7 +854,14 @@ impl Graphics {
                 render_pass.set_pipeline(task.pipeline);
 
                 for (i, bind_group) in task.bind_groups.iter().enumerate() {
-                    render_pass.set_bind_group(i as u32, bind_group, &[]);
+                    let offsets = task.dynamic_offsets.get(i)
+                        .map(|offsets| offsets.as_slice())
+                        .unwrap_or(&[]);
+                    render_pass.set_bind_group(i as u32, bind_group, offsets);
+                }
+
+                if !task.push_constants.is_empty() {
+                    render_pass.set_push_constants(task.push_constant_stages, 0, task.push_constants);
                 }
                 render_pass.set_vertex_buffer(0, task.vertex_buffer.slice(..));
                 render_pass.set_index_buffer(task.index_buffer.slice(..), wgpu::IndexFormat::Uint32); // 1.
@@ -394,41 +869,229 @@ impl Graphics {
             }
         }
 
+        if let Some((query_set, query)) = timestamp {
+            encoder.write_timestamp(query_set, query + 1);
+        }
+
         self.command_buffers.push(encoder.finish());
 
         Ok(())
     }
 
     fn flush(&mut self) {
+        let surface_texture = self.current_surface_texture.take();
+        //taken out while capturing so it isn't borrowed from self
+        let offscreen_texture = self.offscreen_texture.take();
+
+        if let Some(target) = self.pending_capture.take() {
+            let texture = surface_texture.as_ref()
+                .map(|surface_texture| &surface_texture.texture)
+                .or(offscreen_texture.as_ref())
+                .expect("Must call begin render before flush");
+
+            let pixels = self.read_texture(texture);
+            match target {
+                CaptureTarget::File(path) => {
+                    self.capture_result = Some(pixels.and_then(|pixels| {
+                        pixels.save(&path)
+                            .map_err(|e| SimpleError::new(format!("Failed to save capture to {}: {}", path, e)))?;
+                        Ok(pixels)
+                    }));
+                }
+                CaptureTarget::Memory => self.capture_result = Some(pixels),
+            }
+        }
+        self.offscreen_texture = offscreen_texture;
+
+        self.resolve_timestamps();
+
         let command_buffers = self.command_buffers.drain(0..).collect::<Vec<_>>();
         self.queue.submit(command_buffers);
 
-        let surface_texture = self.current_surface_texture.take()
-            .expect("Must call begin render before flush");
+        self.read_timestamps();
 
-        surface_texture.present();
+        if let Some(surface_texture) = surface_texture {
+            surface_texture.present();
+        }
+    }
+
+    fn resolve_timestamps(&mut self) {
+        let timer = match self.gpu_timer.as_ref() {
+            Some(timer) if timer.next_query > 0 => timer,
+            _ => return
+        };
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Timestamp Encoder"),
+        });
+
+        encoder.resolve_query_set(&timer.query_set, 0..timer.next_query, &timer.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &timer.resolve_buffer, 0, 
+            &timer.read_buffer, 0, 
+            timer.next_query as u64 * std::mem::size_of::<u64>() as u64
+        );
+
+        self.command_buffers.push(encoder.finish());
+    }
+
+    //blocks until this frame's timestamps are back from the gpu
+    fn read_timestamps(&mut self) {
+        let timer = match self.gpu_timer.as_mut() {
+            Some(timer) if timer.next_query > 0 => timer,
+            _ => return
+        };
+
+        let query_count = timer.next_query;
+        timer.next_query = 0;
+
+        let buffer_slice = timer.read_buffer.slice(..query_count as u64 * std::mem::size_of::<u64>() as u64);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| { sender.send(result).ok(); });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        if !matches!(receiver.recv(), Ok(Ok(()))) {
+            eprintln!("Failed to read back gpu timestamps");
+            return
+        }
+
+        //timestamps are in ticks, the period converts them to nanoseconds
+        let period = self.queue.get_timestamp_period() as f64;
+        let passes = {
+            let mapped = buffer_slice.get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&mapped);
+            timestamps.chunks(2)
+                .map(|pass| Duration::from_nanos((pass[1].saturating_sub(pass[0]) as f64 * period) as u64))
+                .collect::<Vec<_>>()
+        };
+        timer.read_buffer.unmap();
+
+        self.gpu_timings = Some(GpuTimings { passes });
+    }
+
+    //the surface is srgb so the bytes read back are already srgb encoded, which is what png expects
+    //copies the texture back to the cpu as rgba, waiting on everything submitted before it
+    fn read_texture(&mut self, texture: &wgpu::Texture) -> Result<image::RgbaImage, SimpleError> {
+        if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(SimpleError::new("Surface does not support being copied from"));
+        }
+
+        let swap_red_blue = match self.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(SimpleError::new(format!("Can't capture surface with format {:?}", format)))
+        };
+
+        let width = self.config.width;
+        let height = self.config.height;
+
+        //rows copied out of a texture have to be padded to a multiple of 256 bytes
+        let unpadded_bytes_per_row = 4 * width;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+
+        self.command_buffers.push(encoder.finish());
+        self.submit_and_wait();
+
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| { sender.send(result).ok(); });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        receiver.recv()
+            .map_err(|_| SimpleError::new("Capture buffer was never mapped"))?
+            .map_err(|e| SimpleError::new(format!("Failed to map capture buffer: {}", e)))?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        {
+            let padded_pixels = buffer_slice.get_mapped_range();
+            for row in padded_pixels.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        buffer.unmap();
+
+        if swap_red_blue {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or(SimpleError::new("Captured pixels don't match the surface size"))
+    }
+
+    fn submit_and_wait(&mut self) {
+        let command_buffers = self.command_buffers.drain(0..).collect::<Vec<_>>();
+        let submission = self.queue.submit(command_buffers);
+        self.device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth_texture = Self::create_depth_texture(&self.device, &self.config, "Some depth texture");
+        if new_size.width == 0 || new_size.height == 0 || new_size == self.size {
+            return
         }
+
+        self.size = new_size;
+        self.config.width = new_size.width;
+        self.config.height = new_size.height;
+        self.reconfigure();
     }
-}
 
+    //the depth texture is only recreated alongside the surface, and only when its size no longer matches
+    fn reconfigure(&mut self) {
+        match self.surface.as_ref() {
+            Some(surface) => surface.configure(&self.device, &self.config),
+            None => {
+                let offscreen_size = self.offscreen_texture.as_ref()
+                    .map(|texture| (texture.width(), texture.height()));
+                if offscreen_size != Some((self.config.width, self.config.height)) {
+                    self.offscreen_texture = Some(Self::create_offscreen_texture(&self.device, &self.config));
+                }
+            }
+        }
 
-impl Graphics {
-    fn size(&self) -> PhysicalSize<u32> { self.size }
+        let depth_texture = &self.depth_texture.0;
+        if depth_texture.width() != self.config.width || depth_texture.height() != self.config.height {
+            self.depth_texture = Self::create_depth_texture(&self.device, &self.config, "Some depth texture");
+        }
+    }
 }
 
 impl Graphics {
-fn create_texture<P, S>(&self, image: ImageBuffer<P, S>) -> Result<wgpu::Texture, SimpleError>
+//mips are generated on the cpu by repeatedly halving the image
+fn create_texture<P, S>(&self, image: ImageBuffer<P, S>, generate_mips: bool) -> Result<wgpu::Texture, SimpleError>
 where 
-    P: image::Pixel<Subpixel = u8>,
+    P: image::Pixel<Subpixel = u8> + 'static,
     S: Deref<Target = [<P as image::Pixel>::Subpixel]>,
 {
     let format = match P::CHANNEL_COUNT {
@@ -445,10 +1108,16 @@ where
         depth_or_array_layers: 1,
     };
 
+    let mip_level_count = if generate_mips {
+        32 - dimensions.0.max(dimensions.1).max(1).leading_zeros()
+    } else {
+        1
+    };
+
     let diffuse_texture = self.device.create_texture(
         &wgpu::TextureDescriptor {
             size: texture_size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
@@ -474,17 +1143,41 @@ where
         texture_size,
     );
 
+    for mip_level in 1..mip_level_count {
+        let width = (dimensions.0 >> mip_level).max(1);
+        let height = (dimensions.1 >> mip_level).max(1);
+        let mip = image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle);
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &diffuse_texture,
+                mip_level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &mip,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(P::CHANNEL_COUNT as u32 * width),
+                rows_per_image: std::num::NonZeroU32::new(height),
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+    }
+
     Ok(diffuse_texture)
 }
 
-fn create_sampler(&self) -> wgpu::Sampler {
+fn create_sampler(&self, config: &SamplerConfig) -> wgpu::Sampler {
     self.device.create_sampler(&wgpu::SamplerDescriptor {
-        address_mode_u: wgpu::AddressMode::ClampToEdge,
-        address_mode_v: wgpu::AddressMode::ClampToEdge,
-        address_mode_w: wgpu::AddressMode::ClampToEdge,
-        mag_filter: wgpu::FilterMode::Linear,
-        min_filter: wgpu::FilterMode::Nearest,
-        mipmap_filter: wgpu::FilterMode::Nearest,
+        address_mode_u: config.address_mode_u,
+        address_mode_v: config.address_mode_v,
+        address_mode_w: config.address_mode_w,
+        mag_filter: config.mag_filter,
+        min_filter: config.min_filter,
+        mipmap_filter: config.mipmap_filter,
+        lod_min_clamp: config.lod_min_clamp,
+        lod_max_clamp: config.lod_max_clamp,
         ..Default::default()
     })
 }
@@ -492,10 +1185,12 @@ fn create_sampler(&self) -> wgpu::Sampler {
 fn create_bind_groups(&self, 
         bind_group_layouts: &[(u32, wgpu::BindGroupLayout)], 
         uniforms: &[(String, (u32, u32), MaterialValue)], 
+        dynamic_uniforms: &HashMap<String, Vec<MaterialValue>>,
         textures: &HashMap<Uuid, wgpu::TextureView>,
         samplers: &HashMap<Uuid, wgpu::Sampler>,
-    ) -> Result<Vec<wgpu::BindGroup>, SimpleError> {
+    ) -> Result<(Vec<wgpu::BindGroup>, Vec<Vec<u32>>), SimpleError> {
     let mut bind_groups = Vec::new();
+    let mut dynamic_strides = Vec::new();
     for group_index in 0.. {
         let bind_group_layout = bind_group_layouts.iter().find(|(index, _)| group_index == *index);
         if bind_group_layout.is_none() {
@@ -506,8 +1201,8 @@ fn create_bind_groups(&self,
         //create the offsets here for each group
         let mut groups = Vec::new();
         let mut byte_buffer: Vec<u8> = Vec::new();
-        for (_, (group, binding), value) in uniforms {
-            if *group != group_index {
+        for (name, (group, binding), value) in uniforms {
+            if *group != group_index || dynamic_uniforms.contains_key(name) {
                 continue
             }
             
@@ -518,6 +1213,59 @@ fn create_bind_groups(&self,
         }
         let buffer = self.create_uniform_buffer(&byte_buffer);
 
+        //dynamic uniforms each get their own buffer with every instance at an aligned stride
+        let mut dynamic_buffers = Vec::new();
+        for (name, (group, binding), _) in uniforms {
+            if *group != group_index {
+                continue
+            }
+
+            if let Some(instances) = dynamic_uniforms.get(name) {
+                let (dynamic_buffer, size, stride) = self.create_dynamic_uniform_buffer(name, instances)?;
+                dynamic_buffers.push((*binding, dynamic_buffer, size, stride));
+            }
+        }
+        //offsets are passed in binding order when the group is set
+        dynamic_buffers.sort_by(|(binding_1, ..), (binding_2, ..)| binding_1.cmp(binding_2));
+
+        let mut storage_buffers = Vec::new();
+        for (name, (group, binding), value) in uniforms {
+            if *group != group_index {
+                continue
+            }
+
+            if let MaterialValue::Storage(bytes) = value {
+                storage_buffers.push((*binding, self.create_storage_buffer(name, bytes)?));
+            }
+        }
+
+        //binding arrays borrow every view and sampler they hold, so they're gathered before the entries
+        let mut texture_arrays = Vec::new();
+        let mut sampler_arrays = Vec::new();
+        for (name, (group, binding), value) in uniforms {
+            if *group != group_index {
+                continue
+            }
+
+            match value {
+                MaterialValue::TextureArray(array) => {
+                    let views = array.iter()
+                        .map(|texture| texture.uuid.and_then(|uuid| textures.get(&uuid))
+                            .ok_or(SimpleError::new(&format!("Cannot find every texture assigned to material at: {}", name))))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    texture_arrays.push((*binding, views));
+                },
+                MaterialValue::SamplerArray(array) => {
+                    let array_samplers = array.iter()
+                        .map(|sampler| sampler.uuid.and_then(|uuid| samplers.get(&uuid))
+                            .ok_or(SimpleError::new(&format!("Cannot find every sampler assigned to material at: {}", name))))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    sampler_arrays.push((*binding, array_samplers));
+                },
+                _ => {}
+            }
+        }
+
         let mut offset = 0;
         let mut entries = Vec::new();
         for (name, (group, binding), value) in uniforms {
@@ -526,7 +1274,18 @@ fn create_bind_groups(&self,
             }
             
             let entry =
-            if let Some((_, size)) = groups.iter()
+            if let Some((_, dynamic_buffer, size, _)) = dynamic_buffers.iter()
+                .find(|(dynamic_binding, ..)| dynamic_binding == binding)
+            {
+                wgpu::BindGroupEntry {
+                    binding: *binding,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: dynamic_buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(*size),
+                    })
+                }
+            } else if let Some((_, size)) = groups.iter()
                 .find(|(groups_binding, _)| *groups_binding == binding)
             {
                 let size = *size as u64;
@@ -566,7 +1325,37 @@ fn create_bind_groups(&self,
                             resource: wgpu::BindingResource::Sampler(sampler)
                         }
                     },
-                    _ => panic!("Can't create bind group entry for uniform {}", name)
+                    crate::shader_types::MaterialValue::Storage(_) => {
+                        let (_, storage_buffer) = storage_buffers.iter()
+                            .find(|(storage_binding, _)| storage_binding == binding)
+                            .ok_or(SimpleError::new(&format!("Could not find storage buffer for: {}", name)))?;
+
+                        wgpu::BindGroupEntry {
+                            binding: *binding,
+                            resource: storage_buffer.as_entire_binding(),
+                        }
+                    },
+                    crate::shader_types::MaterialValue::TextureArray(_) => {
+                        let (_, views) = texture_arrays.iter()
+                            .find(|(array_binding, _)| array_binding == binding)
+                            .ok_or(SimpleError::new(&format!("Could not find texture array for: {}", name)))?;
+
+                        wgpu::BindGroupEntry {
+                            binding: *binding,
+                            resource: wgpu::BindingResource::TextureViewArray(views),
+                        }
+                    },
+                    crate::shader_types::MaterialValue::SamplerArray(_) => {
+                        let (_, array_samplers) = sampler_arrays.iter()
+                            .find(|(array_binding, _)| array_binding == binding)
+                            .ok_or(SimpleError::new(&format!("Could not find sampler array for: {}", name)))?;
+
+                        wgpu::BindGroupEntry {
+                            binding: *binding,
+                            resource: wgpu::BindingResource::SamplerArray(array_samplers),
+                        }
+                    },
+                    _ => return Err(SimpleError::new(format!("Can't create bind group entry for uniform {}", name)))
                 }
             };
             entries.push(entry);
@@ -579,12 +1368,39 @@ fn create_bind_groups(&self,
         };
 
         bind_groups.push(self.device.create_bind_group(&binding_descriptor));
+        dynamic_strides.push(dynamic_buffers.iter().map(|(_, _, _, stride)| *stride).collect());
+    }
+
+    Ok((bind_groups, dynamic_strides))
+}
+
+fn create_dynamic_uniform_buffer(&self, name: &str, instances: &[MaterialValue]) -> Result<(Buffer, u64, u32), SimpleError> {
+    let alignment = self.device.limits().min_uniform_buffer_offset_alignment as usize;
+
+    let instance_bytes = instances.iter()
+        .map(|value| value.as_bytes()
+            .ok_or(SimpleError::new(format!("Dynamic uniform {} must be stored in a buffer", name))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let size = instance_bytes.first().map(|bytes| bytes.len()).unwrap_or(0);
+    let stride = size.div_ceil(alignment) * alignment;
+
+    let mut byte_buffer = vec![0u8; stride * instance_bytes.len()];
+    for (i, bytes) in instance_bytes.iter().enumerate() {
+        byte_buffer[i * stride..i * stride + bytes.len()].copy_from_slice(bytes);
     }
 
-    Ok(bind_groups)
+    Ok((self.create_uniform_buffer(&byte_buffer), size as u64, stride as u32))
 }
 
-fn load_pipeline(&mut self, pipeline: Pipeline) -> LoadedPipeline {
+fn load_pipeline(&mut self, pipeline: Pipeline) -> Result<LoadedPipeline, Error> {
+    if !pipeline.push_constant_ranges().is_empty() && !self.device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+        return Err(Error::PushConstantsUnsupported);
+    }
+    if pipeline.uses_binding_arrays() && !self.device.features().contains(wgpu::Features::TEXTURE_BINDING_ARRAY) {
+        return Err(Error::BindingArraysUnsupported);
+    }
+
     let material_bind_groups = pipeline.bind_groups();
 
     let mut group_index = 0;
@@ -597,7 +1413,7 @@ fn load_pipeline(&mut self, pipeline: Pipeline) -> LoadedPipeline {
                 binding: layout.binding.binding,
                 visibility: layout.visibility,
                 ty: layout.binding_type,
-                count: None,
+                count: layout.count,
             })
         }
 
@@ -615,7 +1431,7 @@ fn load_pipeline(&mut self, pipeline: Pipeline) -> LoadedPipeline {
         self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
             bind_group_layouts: bind_group_layouts.as_slice(),
-            push_constant_ranges: &[],
+            push_constant_ranges: pipeline.push_constant_ranges(),
         });
     
     let shader = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -657,8 +1473,8 @@ fn load_pipeline(&mut self, pipeline: Pipeline) -> LoadedPipeline {
         //TODO: implement in material
         depth_stencil: Some(wgpu::DepthStencilState {
             format: Self::DEPTH_FORMAT,
-            depth_write_enabled: true,
-            depth_compare: wgpu::CompareFunction::Less, // 1.
+            depth_write_enabled: pipeline.depth_written(),
+            depth_compare: if pipeline.depth_tested() { wgpu::CompareFunction::Less } else { wgpu::CompareFunction::Always }, // 1.
             stencil: wgpu::StencilState::default(), // 2.
             bias: wgpu::DepthBiasState::default(),
         }),
@@ -670,7 +1486,28 @@ fn load_pipeline(&mut self, pipeline: Pipeline) -> LoadedPipeline {
         multiview: None,
     });
 
-    LoadedPipeline { pipeline: render_pipeline, bind_group_layouts: group_and_bind_group_layouts }
+    Ok(LoadedPipeline { pipeline: render_pipeline, bind_group_layouts: group_and_bind_group_layouts })
+}
+
+//reuses the previous buffers when the new geometry fits, otherwise falls back to creating new ones
+fn write_geometry(&self, previous: Option<GeometryBuffers>, vertices: &[Vertex], indices: &[u32]) -> GeometryBuffers {
+    let (vertex_buffer, index_buffer) = match previous {
+        Some(GeometryBuffers { vertex_buffer, index_buffer, .. }) => (Some(vertex_buffer), Some(index_buffer)),
+        None => (None, None)
+    };
+
+    let vertex_buffer = self.write_buffer(vertex_buffer, bytemuck::cast_slice(vertices))
+        .unwrap_or_else(|| self.create_vertex_buffer(vertices));
+    let index_buffer = self.write_buffer(index_buffer, bytemuck::cast_slice(indices))
+        .unwrap_or_else(|| self.create_index_buffer(indices));
+
+    GeometryBuffers { vertex_buffer, index_buffer, num_indices: indices.len() as u32 }
+}
+
+fn write_buffer(&self, buffer: Option<Buffer>, bytes: &[u8]) -> Option<Buffer> {
+    let buffer = buffer.filter(|buffer| buffer.size() >= bytes.len() as u64)?;
+    self.queue.write_buffer(&buffer, 0, bytes);
+    Some(buffer)
 }
 
 fn create_vertex_buffer(&self, vertices: &[Vertex]) -> Buffer {
@@ -678,7 +1515,7 @@ fn create_vertex_buffer(&self, vertices: &[Vertex]) -> Buffer {
         &wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             contents: bytemuck::cast_slice(vertices),
-            usage: wgpu::BufferUsages::VERTEX,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
         }
     )
 }
@@ -688,11 +1525,29 @@ fn create_index_buffer(&self, indices: &[u32]) -> Buffer {
         &wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
             contents: bytemuck::cast_slice(indices),
-            usage: wgpu::BufferUsages::INDEX,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
         }
     )
 }
 
+fn create_storage_buffer(&self, name: &str, bytes: &[u8]) -> Result<Buffer, SimpleError> {
+    if bytes.is_empty() {
+        return Err(SimpleError::new(format!("Storage buffer {} was never given any data", name)));
+    }
+
+    //storage bindings have to be a multiple of 4 bytes
+    let mut contents = bytes.to_vec();
+    contents.resize(bytes.len().div_ceil(4) * 4, 0);
+
+    Ok(self.device.create_buffer_init(
+        &wgpu::util::BufferInitDescriptor {
+            label: Some("Storage buffer"),
+            contents: &contents,
+            usage: wgpu::BufferUsages::STORAGE,
+        }
+    ))
+}
+
 fn create_uniform_buffer(&self, bytes: &[u8]) -> Buffer {
     self.device.create_buffer_init(
         &wgpu::util::BufferInitDescriptor {
@@ -703,38 +1558,15 @@ fn create_uniform_buffer(&self, bytes: &[u8]) -> Buffer {
     )
 }
 
-async fn new(window: &Window) -> Graphics {
+async fn new(window: &Window) -> Result<Graphics, Error> {
     let size = window.inner_size();
 
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Default::default(),
-    });
+    let instance = Self::create_instance();
     
-    let surface = unsafe { instance.create_surface(&window) }.unwrap();
-
-    let adapter = instance.request_adapter(
-        &wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        },
-    ).await.unwrap();
+    let surface = unsafe { instance.create_surface(&window) }?;
 
-    let (device, queue) = adapter.request_device(
-        &wgpu::DeviceDescriptor {
-            features: wgpu::Features::empty(),
-            // WebGL doesn't support all of wgpu's features, so if
-            // we're building for the web we'll have to disable some.
-            limits: if cfg!(target_arch = "wasm32") {
-                wgpu::Limits::downlevel_webgl2_defaults()
-            } else {
-                wgpu::Limits::default()
-            },
-            label: None,
-        },
-        None, // Trace path
-    ).await.unwrap();
+    let adapter = Self::request_adapter(&instance, Some(&surface)).await?;
+    let (device, queue) = Self::request_device(&adapter).await?;
 
     let surface_caps = surface.get_capabilities(&adapter);
 
@@ -743,8 +1575,15 @@ async fn new(window: &Window) -> Graphics {
         .cloned()
         .unwrap_or(surface_caps.formats[0]);
 
+    //copying out of the surface is what lets frames be captured
+    let usage = if surface_caps.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+        wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+    } else {
+        wgpu::TextureUsages::RENDER_ATTACHMENT
+    };
+
     let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        usage,
         format: surface_format,
         width: size.width,
         height: size.height,
@@ -754,20 +1593,129 @@ async fn new(window: &Window) -> Graphics {
     };
     surface.configure(&device, &config);
 
+    Ok(Self::from_parts(instance, Some(surface), None, adapter, device, queue, config))
+}
+
+//the config describes the offscreen texture, it's never used to configure a surface
+async fn new_headless(width: u32, height: u32) -> Result<Graphics, Error> {
+    let instance = Self::create_instance();
+
+    let adapter = Self::request_adapter(&instance, None).await?;
+    let (device, queue) = Self::request_device(&adapter).await?;
+
+    let config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width: width.max(1),
+        height: height.max(1),
+        present_mode: wgpu::PresentMode::Fifo,
+        alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+    };
+    let offscreen_texture = Self::create_offscreen_texture(&device, &config);
+
+    Ok(Self::from_parts(instance, None, Some(offscreen_texture), adapter, device, queue, config))
+}
+
+fn from_parts(
+    instance: Instance, 
+    surface: Option<Surface>, 
+    offscreen_texture: Option<wgpu::Texture>,
+    adapter: Adapter, 
+    device: Device, 
+    queue: Queue, 
+    config: SurfaceConfiguration
+) -> Graphics {
     let depth_texture = Self::create_depth_texture(&device, &config, "Some depth texture");
 
     Graphics {
         _instance: instance,
         surface,
+        offscreen_texture,
         _adapter: adapter,
         device,
         queue,
+        size: PhysicalSize::new(config.width, config.height),
         config,
-        size,
         depth_texture,
+        clear_color: [0f32, 0f32, 0f32],
         current_surface_texture: None,
-        command_buffers: Vec::new()
+        command_buffers: Vec::new(),
+        pending_capture: None,
+        capture_result: None,
+        gpu_timer: None,
+        gpu_timings: None,
+    }
+}
+
+fn create_instance() -> Instance {
+    wgpu::Instance::new(wgpu::InstanceDescriptor {
+        //WGPU_BACKEND can pick a backend explicitly, e.g. to force vulkan under llvmpipe
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+        dx12_shader_compiler: Default::default(),
+    })
+}
+
+async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), Error> {
+    //push constants, timestamps and binding arrays are only turned on where the adapter supports them
+    let features = adapter.features() & (wgpu::Features::PUSH_CONSTANTS | wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TEXTURE_BINDING_ARRAY);
+
+    // WebGL doesn't support all of wgpu's features, so if
+    // we're building for the web we'll have to disable some.
+    let mut limits = if cfg!(target_arch = "wasm32") {
+        wgpu::Limits::downlevel_webgl2_defaults()
+    } else {
+        wgpu::Limits::default()
+    };
+    if features.contains(wgpu::Features::PUSH_CONSTANTS) {
+        limits.max_push_constant_size = adapter.limits().max_push_constant_size;
+    }
+
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            features,
+            limits,
+            label: None,
+        },
+        None, // Trace path
+    ).await?;
+
+    Ok((device, queue))
+}
+
+fn create_offscreen_texture(device: &Device, config: &SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Texture"),
+        size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
+//falls back to a software adapter when no hardware adapter can present to the surface
+async fn request_adapter(instance: &Instance, surface: Option<&Surface>) -> Result<Adapter, Error> {
+    for force_fallback_adapter in [false, true] {
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: surface,
+                force_fallback_adapter,
+            },
+        ).await;
+
+        if let Some(adapter) = adapter {
+            if force_fallback_adapter {
+                eprintln!("No hardware graphics adapter found, using fallback adapter {}", adapter.get_info().name);
+            }
+            return Ok(adapter)
+        }
     }
+
+    Err(Error::NoAdapter)
 }
 
 const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float; // 1.
@@ -812,4 +1760,137 @@ fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfigurati
     (texture, view, sampler)
 }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //tests that need a gpu are ignored by default, run them with cargo test -- --ignored
+    fn headless(width: u32, height: u32) -> Renderer {
+        Renderer::headless(width, height).expect("Gpu tests need a graphics adapter")
+    }
+
+    //each color channel is its own dynamic uniform so they can be given different numbers of instances
+    const DYNAMIC_COLOR_SHADER: &str = "
+        struct VertexInput {
+            @location(0) position: vec3<f32>,
+            @location(1) color: vec3<f32>,
+            @location(2) tex_coords: vec2<f32>,
+        };
+
+        @group(0) @binding(0)
+        var<uniform> red: f32;
+        @group(0) @binding(1)
+        var<uniform> green: f32;
+
+        @vertex
+        fn vs_main(model: VertexInput) -> @builtin(position) vec4<f32> {
+            return vec4<f32>(model.position, 1.0);
+        }
+
+        @fragment
+        fn fs_main() -> @location(0) vec4<f32> {
+            return vec4<f32>(red, green, 0.0, 1.0);
+        }
+    ";
+
+    fn render_image(renderer: &mut Renderer, world: &World) -> image::RgbaImage {
+        renderer.capture_frame_image();
+        renderer.render(world).unwrap();
+        renderer.take_capture_result()
+            .expect("A frame was rendered")
+            .unwrap()
+    }
+
+    #[test]
+    fn unbound_uniforms_lists_every_texture_and_sampler() {
+        use crate::shader_types::{Texture, Sampler};
+
+        let loaded = Uuid::new_v4();
+        let textures = HashMap::from([(loaded, ())]);
+        let samplers = HashMap::from([(loaded, ())]);
+
+        let material = Material::new(vec![
+            (String::from("t_diffuse"), (0, 0), MaterialValue::Texture(Texture::default())),
+            (String::from("s_diffuse"), (0, 1), MaterialValue::Sampler(Sampler::default())),
+            (String::from("t_normal"), (0, 2), MaterialValue::Texture(Texture::new(Uuid::new_v4()))),
+            (String::from("s_normal"), (0, 3), MaterialValue::Sampler(Sampler::new(Uuid::new_v4()))),
+            (String::from("t_loaded"), (0, 4), MaterialValue::Texture(Texture::new(loaded))),
+            (String::from("s_loaded"), (0, 5), MaterialValue::Sampler(Sampler::new(loaded))),
+        ]);
+
+        assert_eq!(unbound_uniforms(&material, &textures, &samplers), vec![
+            String::from("Material was never assigned texture at: t_diffuse"),
+            String::from("Material was never assigned sampler at: s_diffuse"),
+            String::from("Cannot find texture assigned to material at: t_normal"),
+            String::from("Cannot find sampler assigned to material at: s_normal"),
+        ]);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn draws_use_their_own_dynamic_offsets() {
+        let mut renderer = headless(16, 4);
+        renderer.set_clear_color([0.0, 0.0, 1.0]);
+
+        let pipeline = Pipeline::load::<Vertex>(DYNAMIC_COLOR_SHADER).unwrap()
+            .dynamic_uniform("red").unwrap()
+            .dynamic_uniform("green").unwrap();
+        let pipeline_handle = renderer.create_pipeline(pipeline).unwrap();
+        let material = renderer.create_material(pipeline_handle).unwrap();
+
+        //red only has instance 0 and green only goes up to instance 1, instance 3 is never written
+        renderer.update_material_instance(material, "red", 0, 1f32).unwrap();
+        renderer.update_material_instance(material, "green", 1, 1f32).unwrap();
+
+        //quarters of the screen in clip space, the last quarter is left clear
+        let mut world = World::default();
+        for (quarter, index) in [(0, 0), (1, 1), (2, 3)] {
+            let rectangle = crate::graphics::RectangleBuilder::default()
+                .position(-1.0 + 0.5 * quarter as f32, -1.0)
+                .size(0.5, 2.0)
+                .depth(0.5)
+                .build();
+            world.push((material, rectangle, RenderStage { order: 0 }, UniformInstance { index }));
+        }
+
+        let image = render_image(&mut renderer, &world);
+
+        assert_eq!(image.get_pixel(2, 2).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(6, 2).0, [0, 255, 0, 255]);
+        assert_eq!(image.get_pixel(10, 2).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(14, 2).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn clear_color_reads_back_exactly() {
+        let mut renderer = headless(16, 16);
+        renderer.set_clear_color([1.0, 0.0, 1.0]);
+
+        let image = render_image(&mut renderer, &World::default());
+
+        assert_eq!(image.dimensions(), (16, 16));
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 255, 255]));
+    }
+
+    #[test]
+    #[ignore = "needs a graphics adapter"]
+    fn captured_png_has_the_clear_color() {
+        let mut renderer = headless(16, 16);
+        renderer.set_clear_color([0.0, 1.0, 0.0]);
+
+        let path = std::env::temp_dir().join("sd_land_capture_test.png");
+        renderer.capture_frame(path.to_str().unwrap());
+        renderer.render(&World::default()).unwrap();
+        renderer.take_capture_result()
+            .expect("A frame was rendered")
+            .unwrap();
+
+        let image = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(image.get_pixel(8, 8).0, [0, 255, 0, 255]);
+    }
+}
@@ -1,8 +1,9 @@
 use core::slice;
-use std::{collections::HashMap, any::Any, num::NonZeroU64, mem, ptr};
+use std::{collections::HashMap, any::Any, num::{NonZeroU32, NonZeroU64}, mem, ptr};
 
 use cgmath::SquareMatrix;
 use naga::{ImageDimension, ImageClass, ScalarKind};
+use simple_error::SimpleError;
 use uuid::Uuid;
 
 #[derive(Debug, Clone)]
@@ -94,7 +95,15 @@ impl Matrix {
     }
 }
 
-pub fn create_binding_type(naga_type: &naga::TypeInner) -> Option<wgpu::BindingType> {    
+pub fn create_binding_type(naga_type: &naga::TypeInner, space: naga::AddressSpace, types: &naga::UniqueArena<naga::Type>) -> Option<wgpu::BindingType> {    
+    //binding arrays bind several resources of their base type at once, binding_array_count gives how many
+    if let naga::TypeInner::BindingArray { base, .. } = naga_type {
+        return match &types.get_handle(*base).ok()?.inner {
+            naga::TypeInner::BindingArray { .. } => None,
+            base => create_binding_type(base, space, types),
+        };
+    }
+
     let size = match &naga_type {
         naga::TypeInner::Scalar {width, .. } => 
             Some(NonZeroU64::new(*width as u64)),
@@ -104,12 +113,25 @@ pub fn create_binding_type(naga_type: &naga::TypeInner) -> Option<wgpu::BindingT
             Some(NonZeroU64::new(*columns as u64 * *rows as u64 * (*width as u64))),
         naga::TypeInner::Struct { span, .. } =>
             Some(NonZeroU64::new(*span as u64)),
+        //arrays are only backed by raw bytes, so they have to be storage buffers.
+        //runtime sized arrays need at least one element, fixed size arrays are checked at draw time
+        naga::TypeInner::Array { stride, size, .. } if matches!(space, naga::AddressSpace::Storage { .. }) => match size {
+            naga::ArraySize::Dynamic => Some(NonZeroU64::new(*stride as u64)),
+            naga::ArraySize::Constant(_) => Some(None),
+        },
         _ => None
     };
     
     if let Some(size) = size {
+        let ty = match space {
+            naga::AddressSpace::Storage { access } => wgpu::BufferBindingType::Storage { 
+                read_only: !access.contains(naga::StorageAccess::STORE) 
+            },
+            _ => wgpu::BufferBindingType::Uniform,
+        };
+
         Some(wgpu::BindingType::Buffer { 
-            ty: wgpu::BufferBindingType::Uniform, 
+            ty, 
             has_dynamic_offset: false, 
             min_binding_size: size 
         })
@@ -127,6 +149,20 @@ pub fn create_binding_type(naga_type: &naga::TypeInner) -> Option<wgpu::BindingT
 
 }
 
+//the count for the layout entry, None for anything that isn't a binding array
+pub fn binding_array_count(naga_type: &naga::TypeInner, constants: &naga::Arena<naga::Constant>) -> Result<Option<NonZeroU32>, SimpleError> {
+    match naga_type {
+        naga::TypeInner::BindingArray { size: naga::ArraySize::Constant(handle), .. } => constants[*handle]
+            .to_array_length()
+            .and_then(NonZeroU32::new)
+            .map(Some)
+            .ok_or(SimpleError::new("Binding array length must be a positive constant")),
+        naga::TypeInner::BindingArray { size: naga::ArraySize::Dynamic, .. } => 
+            Err(SimpleError::new("Binding arrays need a fixed length")),
+        _ => Ok(None)
+    }
+}
+
 pub fn create_uniform_storage(naga_type: &naga::TypeInner) -> Option<MaterialValue> {
     let value = match naga_type {
         naga::TypeInner::Scalar { kind, ..  } => match kind {
@@ -171,10 +207,38 @@ pub enum MaterialValue {
 
     Texture(Texture),
     Sampler(Sampler),
+    //binding arrays, set with a Vec<Texture> or Vec<Sampler> as long as the array
+    TextureArray(Vec<Texture>),
+    SamplerArray(Vec<Sampler>),
     Struct(HashMap<String, MaterialValue>),
+    //raw contents of a storage buffer, set with a Vec<u8>
+    Storage(Vec<u8>),
 }
 
 impl MaterialValue {
+    //rust type set_uniform expects for this value, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        fn name_of<T>(_: &T) -> &'static str { std::any::type_name::<T>() }
+
+        match self {
+            MaterialValue::Float(v) => name_of(v),
+            MaterialValue::Int(v) => name_of(v),
+            MaterialValue::Uint(v) => name_of(v),
+            MaterialValue::Bool(v) => name_of(v),
+            MaterialValue::FloatVector(v) => name_of(v),
+            MaterialValue::IntVector(v) => name_of(v),
+            MaterialValue::UintVector(v) => name_of(v),
+            MaterialValue::BoolVector(v) => name_of(v),
+            MaterialValue::Matrix(v) => name_of(v),
+            MaterialValue::Texture(v) => name_of(v),
+            MaterialValue::Sampler(v) => name_of(v),
+            MaterialValue::TextureArray(v) => name_of(v),
+            MaterialValue::SamplerArray(v) => name_of(v),
+            MaterialValue::Struct(v) => name_of(v),
+            MaterialValue::Storage(v) => name_of(v),
+        }
+    }
+
     pub fn as_bytes(&self) -> Option<Vec<u8>> {
         let mut vec = Vec::new();
         match self {
@@ -202,8 +266,11 @@ impl MaterialValue {
             MaterialValue::BoolVector(v) => return Some(v.as_bytes()),
             MaterialValue::Matrix(v) => return Some(v.as_bytes()),
             MaterialValue::Struct(_) => return None,
+            MaterialValue::Storage(_) => return None,
             MaterialValue::Texture(_) => return None,
             MaterialValue::Sampler(_) => return None,
+            MaterialValue::TextureArray(_) => return None,
+            MaterialValue::SamplerArray(_) => return None,
         };
 
         Some(vec)
@@ -221,8 +288,11 @@ impl MaterialValue {
             MaterialValue::BoolVector(v) => v,
             MaterialValue::Texture(v) => v,
             MaterialValue::Sampler(v) => v,
+            MaterialValue::TextureArray(v) => v,
+            MaterialValue::SamplerArray(v) => v,
             MaterialValue::Struct(v) => v,
             MaterialValue::Matrix(v) => v,
+            MaterialValue::Storage(v) => v,
         };
 
         any.downcast_mut::<T>()
@@ -240,8 +310,11 @@ impl MaterialValue {
             MaterialValue::BoolVector(v) => v,
             MaterialValue::Texture(v) => v,
             MaterialValue::Sampler(v) => v,
+            MaterialValue::TextureArray(v) => v,
+            MaterialValue::SamplerArray(v) => v,
             MaterialValue::Struct(v) => v,
             MaterialValue::Matrix(v) => v,
+            MaterialValue::Storage(v) => v,
         };
 
         any.downcast_ref::<T>()
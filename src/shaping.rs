@@ -0,0 +1,85 @@
+use simple_error::SimpleError;
+
+//a glyph picked by the shaper, distances are in pixels at the size the run was shaped at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapedGlyph {
+    pub glyph_index: u16,
+    pub x_advance: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+}
+
+//runs text through harfbuzz's shaping rules so ligatures and combining marks come out as the font
+//intends, the glyph indices are the same ones fontdue rasterizes with rasterize_indexed
+pub fn shape(font_bytes: &[u8], text: &str, pixel_size: f32) -> Result<Vec<ShapedGlyph>, SimpleError> {
+    let face = rustybuzz::Face::from_slice(font_bytes, 0)
+        .ok_or(SimpleError::new("Failed to parse font for shaping"))?;
+
+    //positions come back in font units
+    let units_to_pixels = pixel_size / face.units_per_em() as f32;
+
+    let mut buffer = rustybuzz::UnicodeBuffer::new();
+    buffer.push_str(text);
+    let shaped = rustybuzz::shape(&face, &[], buffer);
+
+    let glyphs = shaped.glyph_infos().iter()
+        .zip(shaped.glyph_positions())
+        .map(|(info, position)| ShapedGlyph {
+            glyph_index: info.glyph_id as u16,
+            x_advance: position.x_advance as f32 * units_to_pixels,
+            x_offset: position.x_offset as f32 * units_to_pixels,
+            y_offset: position.y_offset as f32 * units_to_pixels,
+        })
+        .collect();
+
+    Ok(glyphs)
+}
+
+//where each glyph's origin lands relative to the start of the run, and how wide the run is.
+//letter spacing only goes between glyphs that advance, so combining marks stay on their base
+pub fn position_glyphs(glyphs: &[ShapedGlyph], glyph_scale: f32, letter_spacing: f32) -> (Vec<(u16, f32, f32)>, f32) {
+    let mut positions = Vec::new();
+    let mut x = 0f32;
+
+    for (i, glyph) in glyphs.iter().enumerate() {
+        //marks are offset from the end of their base glyph, so the spacing goes in before the next advancing glyph
+        if i > 0 && glyph.x_advance != 0f32 {
+            x += letter_spacing;
+        }
+
+        positions.push((glyph.glyph_index, x + glyph.x_offset * glyph_scale, glyph.y_offset * glyph_scale));
+        x += glyph.x_advance * glyph_scale;
+    }
+
+    (positions, x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph(glyph_index: u16, x_advance: f32, x_offset: f32, y_offset: f32) -> ShapedGlyph {
+        ShapedGlyph { glyph_index, x_advance, x_offset, y_offset }
+    }
+
+    #[test]
+    fn glyphs_advance_by_their_scaled_width() {
+        let glyphs = [glyph(1, 10f32, 0f32, 0f32), glyph(2, 20f32, 0f32, 0f32), glyph(3, 10f32, 0f32, 0f32)];
+
+        let (positions, width) = position_glyphs(&glyphs, 2f32, 0f32);
+
+        assert_eq!(positions, vec![(1, 0f32, 0f32), (2, 20f32, 0f32), (3, 60f32, 0f32)]);
+        assert_eq!(width, 80f32);
+    }
+
+    #[test]
+    fn combining_marks_sit_on_their_base_glyph() {
+        //an acute accent shaped onto an e, pulled back over it and raised
+        let glyphs = [glyph(1, 10f32, 0f32, 0f32), glyph(2, 0f32, -8f32, 3f32), glyph(3, 10f32, 0f32, 0f32)];
+
+        let (positions, width) = position_glyphs(&glyphs, 1f32, 5f32);
+
+        assert_eq!(positions, vec![(1, 0f32, 0f32), (2, 2f32, 3f32), (3, 15f32, 0f32)]);
+        assert_eq!(width, 25f32);
+    }
+}
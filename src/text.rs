@@ -1,20 +1,54 @@
-use std::{io::Read, fs::File, collections::HashMap, cmp::Ordering};
+use std::{io::Read, fs::File, collections::HashMap, cmp::Ordering, cell::Cell};
 
 use fontdue::Metrics;
 use image::Luma;
 use simple_error::SimpleError;
 
-use crate::{renderer::{MaterialHandle, Renderer, RenderStage}, pipeline::Pipeline, graphics::{Vertex, Rectangle, RectangleBuilder}, shader_types::{Texture, Sampler}};
+use crate::{error::Error, renderer::{MaterialHandle, PipelineHandle, Renderer, RenderStage}, pipeline::Pipeline, graphics::{Vertex, Rectangle, RectangleBuilder}, shader_types::{Texture, Sampler}};
 
 use font_loader::system_fonts;
 
 type TexCoords = [[f32; 2]; 4];
 
+//a scale of 1 lays text out at this pixel size, and it's the size fonts are rasterized at by default
+pub const RASTER_SIZE: f32 = 50f32;
+
+//upper bound on how many sizes a TextBoxFactory keeps atlases for, the least recently used goes first
+const MAX_CACHED_FONT_SIZES: usize = 8;
+
+//how many pixels a signed distance field extends past the edges of a glyph
+const SDF_SPREAD: i32 = 6;
+
+//text is drawn after ui boxes, overlay text goes after overlay boxes since neither is depth tested
+const TEXT_STAGE: u32 = 1;
+const OVERLAY_TEXT_STAGE: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
+
 pub struct Font {
-    characters: HashMap<char, (TexCoords, Metrics)>,
+    //glyph index of every character that can be laid out without shaping
+    #[cfg(not(feature = "shaping"))]
+    characters: HashMap<char, u16>,
+    glyphs: HashMap<u16, (TexCoords, Metrics)>,
     texture: Texture,
     font: fontdue::Font,
+    #[cfg(feature = "shaping")]
+    font_bytes: Vec<u8>,
     smallest_ymin: f32,
+    line_height: f32,
+    raster_size: f32,
 }
 
 impl Font {
@@ -36,17 +70,52 @@ impl Font {
     }
 
     pub fn load(renderer: &mut Renderer, system_font: &str) -> Result<Self, SimpleError> {
+        Self::load_font(renderer, system_font, false, RASTER_SIZE)
+    }
+
+    //rasterizes at a specific pixel size, layout still treats a scale of 1 as RASTER_SIZE
+    pub fn load_sized(renderer: &mut Renderer, system_font: &str, raster_size: f32) -> Result<Self, SimpleError> {
+        Self::load_font(renderer, system_font, false, raster_size)
+    }
+
+    //stores a signed distance field in the atlas instead of coverage, so glyphs stay sharp
+    //when scaled, it has to be drawn with text_sdf_shader.wgsl
+    pub fn load_sdf(renderer: &mut Renderer, system_font: &str) -> Result<Self, SimpleError> {
+        Self::load_font(renderer, system_font, true, RASTER_SIZE)
+    }
+
+    fn load_font(renderer: &mut Renderer, system_font: &str, sdf: bool, raster_size: f32) -> Result<Self, SimpleError> {
         let font_bytes = Self::load_system_font(system_font)?;
 
-        let font = fontdue::Font::from_bytes(font_bytes, fontdue::FontSettings::default()).unwrap();
+        let font = fontdue::Font::from_bytes(font_bytes.as_slice(), fontdue::FontSettings::default())
+            .map_err(|e| SimpleError::new(format!("Failed to parse font {}: {}", system_font, e)))?;
+
+        let characters = (32u8..127)
+            .map(|c| (c as char, font.lookup_glyph_index(c as char)))
+            .collect::<HashMap<_, _>>();
 
-        let width = 127u32 - 32u32;
+        //shaping can substitute any glyph in the font, so all of them go in the atlas
+        #[cfg(feature = "shaping")]
+        let glyph_indices = (0..font.glyph_count()).collect::<Vec<_>>();
+        #[cfg(not(feature = "shaping"))]
+        let glyph_indices = {
+            let mut glyph_indices = characters.values().copied().collect::<Vec<_>>();
+            glyph_indices.sort();
+            glyph_indices.dedup();
+            glyph_indices
+        };
+
+        let glyph_count = glyph_indices.len() as u32;
 
         let mut char_data = Vec::new();
-        for c in 32u8..127 {
-            let c = c as char;
-            let (metrics, bitmap) = font.rasterize(c, 50f32);
-            char_data.push((c, metrics, bitmap));
+        for glyph_index in glyph_indices {
+            let (metrics, bitmap) = font.rasterize_indexed(glyph_index, raster_size);
+            let (metrics, bitmap) = if sdf { 
+                Self::signed_distance_field(metrics, &bitmap) 
+            } else { 
+                (metrics, bitmap) 
+            };
+            char_data.push((glyph_index, metrics, bitmap));
         }
 
         let max_height = char_data.iter()
@@ -59,7 +128,9 @@ impl Font {
             .map(|(_, m, _)| m.width)
             .unwrap() as u32;
 
+        //only the characters count towards the baseline, so shaped text sits where plain text does
         let smallest_ymin = char_data.iter()
+            .filter(|(glyph_index, ..)| characters.values().any(|index| index == glyph_index))
             .min_by(|(_, a, _), (_, b, _)| 
                 a.bounds.ymin.partial_cmp(&b.bounds.ymin)
                     .unwrap_or(Ordering::Equal) 
@@ -67,18 +138,32 @@ impl Font {
             .map(|(_, m, _)| m.bounds.ymin)
             .unwrap();
 
-        let font_image = image::GrayImage::from_fn(max_width * width, max_height, 
+        //a roughly square grid of glyphs, a single row runs past the texture size limit at large sizes
+        let columns = (glyph_count as f32).sqrt().ceil() as u32;
+        let rows = glyph_count.div_ceil(columns);
+
+        let max_dimension = renderer.max_texture_dimension();
+        if max_width * columns > max_dimension || max_height * rows > max_dimension {
+            return Err(SimpleError::new(format!(
+                "A {}px font atlas doesn't fit in the device's {}px texture limit", raster_size, max_dimension
+            )));
+        }
+
+        let font_image = image::GrayImage::from_fn(max_width * columns, max_height * rows, 
             |x, y| {
                 let in_character_x: u32 = x % max_width;
-                let character = (x - in_character_x) / max_width;
-                let (_, metrics, bitmap) = char_data
-                    .get(character as usize)
-                    .expect("Failed to locate character");
-
-                if y >= metrics.height as u32 || in_character_x >= metrics.width as u32 {
+                let in_character_y: u32 = y % max_height;
+                let character = (y / max_height) * columns + x / max_width;
+                let (_, metrics, bitmap) = match char_data.get(character as usize) {
+                    Some(character) => character,
+                    //the last row isn't always full
+                    None => return Luma([0u8])
+                };
+
+                if in_character_y >= metrics.height as u32 || in_character_x >= metrics.width as u32 {
                     Luma([0u8])
                 } else {
-                    let in_character_index = (y * (metrics.width as u32) + in_character_x) as usize;
+                    let in_character_index = (in_character_y * (metrics.width as u32) + in_character_x) as usize;
                     let byte: u8 = *bitmap
                         .get(in_character_index)
                         .unwrap_or(&0u8);
@@ -87,30 +172,128 @@ impl Font {
                 }
             });
 
-        font_image.save("font.jpg").unwrap();
+        let line_height = font.horizontal_line_metrics(raster_size)
+            .map(|line_metrics| line_metrics.new_line_size)
+            .unwrap_or(max_height as f32);
 
-        let texture = Texture::new(renderer.create_texture(font_image).unwrap());
+        let texture = Texture::new(renderer.create_texture(font_image)?);
         
-        let mut characters = HashMap::new();
-        for (i, (c, metrics, _)) in char_data.into_iter().enumerate() {
+        let mut glyphs = HashMap::new();
+        for (i, (glyph_index, metrics, _)) in char_data.into_iter().enumerate() {
             let tex_coords = Self::tex_coords(
-                i as u32, 0, 
-                width, 1, 
+                i as u32 % columns, i as u32 / columns, 
+                columns, rows, 
                 max_height as f32, max_width as f32, 
                 &metrics
             );
 
-            characters.insert(c, (tex_coords, metrics));
+            glyphs.insert(glyph_index, (tex_coords, metrics));
         }
 
         Ok(Self {
+            #[cfg(not(feature = "shaping"))]
             characters,
+            glyphs,
             texture,
             font,
-            smallest_ymin
+            #[cfg(feature = "shaping")]
+            font_bytes,
+            smallest_ymin,
+            line_height,
+            raster_size
         })
     }
 
+    pub fn raster_size(&self) -> f32 { self.raster_size }
+
+    //converts a scale relative to RASTER_SIZE into one relative to this font's glyphs
+    fn glyph_scale(&self, scale: f32) -> f32 { scale * RASTER_SIZE / self.raster_size }
+
+    pub fn line_height(&self, scale: f32) -> f32 { self.line_height * self.glyph_scale(scale) }
+
+    #[cfg(not(feature = "shaping"))]
+    fn glyph(&self, c: char) -> Result<&(TexCoords, Metrics), SimpleError> {
+        self.characters.get(&c)
+            .and_then(|glyph_index| self.glyphs.get(glyph_index))
+            .ok_or(SimpleError::new("That character hasn't been loaded in this font!"))
+    }
+
+    #[cfg(not(feature = "shaping"))]
+    pub fn text_width(&self, text: &str, scale: f32, letter_spacing: f32) -> Result<f32, SimpleError> {
+        let characters = text.chars().collect::<Vec<_>>();
+
+        line_width(&characters, self.glyph_scale(scale), letter_spacing,
+            |c| self.glyph(c).map(|(_, metrics)| metrics.advance_width),
+            |c, next_character| self.font.horizontal_kern(c, next_character, self.raster_size).unwrap_or(0f32))
+    }
+
+    #[cfg(feature = "shaping")]
+    pub fn text_width(&self, text: &str, scale: f32, letter_spacing: f32) -> Result<f32, SimpleError> {
+        let (_, width) = self.glyph_run(text, scale, letter_spacing)?;
+        Ok(width)
+    }
+
+    //breaks text into lines no wider than max_width at this size, see wrap_lines
+    pub fn wrap_text(&self, text: &str, max_width: f32, scale: f32, letter_spacing: f32) -> Result<Vec<String>, SimpleError> {
+        wrap_lines(text, max_width, |line| self.text_width(line, scale, letter_spacing))
+    }
+
+    #[cfg(not(feature = "shaping"))]
+    fn advance(&self, c: char, next_character: Option<char>, scale: f32, letter_spacing: f32) -> Result<f32, SimpleError> {
+        let (_, metrics) = self.glyph(c)?;
+
+        //kerning has to come from the same pixel size as the metrics it's scaled alongside
+        let kerning = next_character.map(|next_character| 
+            self.font.horizontal_kern(c, next_character, self.raster_size).unwrap_or(0f32));
+
+        Ok(glyph_advance(metrics.advance_width, kerning, self.glyph_scale(scale), letter_spacing))
+    }
+
+    //brute force search for the nearest edge within the spread, 0.5 sits on the edge and
+    //the bitmap grows by the spread on every side so the field has room outside the glyph
+    fn signed_distance_field(mut metrics: Metrics, bitmap: &[u8]) -> (Metrics, Vec<u8>) {
+        let width = metrics.width as i32;
+        let height = metrics.height as i32;
+
+        let inside = |x: i32, y: i32| {
+            x >= 0 && y >= 0 && x < width && y < height && bitmap[(y * width + x) as usize] > 127
+        };
+
+        let field_width = width + 2 * SDF_SPREAD;
+        let field_height = height + 2 * SDF_SPREAD;
+
+        let mut field = Vec::with_capacity((field_width * field_height) as usize);
+        for y in 0..field_height {
+            for x in 0..field_width {
+                let bitmap_x = x - SDF_SPREAD;
+                let bitmap_y = y - SDF_SPREAD;
+                let is_inside = inside(bitmap_x, bitmap_y);
+
+                let mut closest = SDF_SPREAD as f32;
+                for dy in -SDF_SPREAD..=SDF_SPREAD {
+                    for dx in -SDF_SPREAD..=SDF_SPREAD {
+                        if inside(bitmap_x + dx, bitmap_y + dy) != is_inside {
+                            closest = closest.min(((dx * dx + dy * dy) as f32).sqrt());
+                        }
+                    }
+                }
+
+                let distance = if is_inside { closest } else { -closest };
+                let value = 0.5 + distance / (2f32 * SDF_SPREAD as f32);
+                field.push((value.clamp(0f32, 1f32) * 255f32) as u8);
+            }
+        }
+
+        metrics.width = field_width as usize;
+        metrics.height = field_height as usize;
+        metrics.bounds.xmin -= SDF_SPREAD as f32;
+        metrics.bounds.ymin -= SDF_SPREAD as f32;
+        metrics.bounds.width += 2f32 * SDF_SPREAD as f32;
+        metrics.bounds.height += 2f32 * SDF_SPREAD as f32;
+
+        (metrics, field)
+    }
+
     fn tex_coords(tile_x: u32, tile_y: u32, width: u32, height: u32, tile_height: f32, tile_width: f32, metrics: &Metrics) -> TexCoords {
         //bottom of the char
         let tile_width_sf = 1f32 / (width as f32);
@@ -125,20 +308,21 @@ impl Font {
         [[left, bottom], [left, top], [right, bottom], [right, top]]
     }
 
-    fn layout_text(&self, text: &str, mut origin: (f32, f32), scale: f32, depth: f32) -> Result<Vec<Rectangle>, SimpleError> {
-        origin.1 += -self.smallest_ymin * scale;
+    fn layout_text(&self, text: &str, mut origin: (f32, f32), scale: f32, depth: f32, letter_spacing: f32) -> Result<Vec<Rectangle>, SimpleError> {
+        let glyph_scale = self.glyph_scale(scale);
+        origin.1 += -self.smallest_ymin * glyph_scale;
         
         let mut rectangles = Vec::new();
-        let characters = text.chars().collect::<Vec<_>>();
-        for (i, c) in characters.iter().enumerate() {
-            let (tex_coords, metrics) = self.characters.get(c)
-                .ok_or(SimpleError::new("That character hasn't been loaded in this font!"))?;
+        let (glyph_run, _) = self.glyph_run(text, scale, letter_spacing)?;
+        for (glyph_index, x, y) in glyph_run {
+            let (tex_coords, metrics) = self.glyphs.get(&glyph_index)
+                .ok_or(SimpleError::new("That glyph hasn't been loaded in this font!"))?;
             
             //get the bottom left position 
-            let bottom = origin.1 + (metrics.bounds.ymin * scale);
-            let height = metrics.bounds.height * scale;
-            let left = origin.0 + (metrics.bounds.xmin * scale);
-            let width = metrics.bounds.width * scale;
+            let bottom = origin.1 + y + (metrics.bounds.ymin * glyph_scale);
+            let height = metrics.bounds.height * glyph_scale;
+            let left = origin.0 + x + (metrics.bounds.xmin * glyph_scale);
+            let width = metrics.bounds.width * glyph_scale;
 
             rectangles.push(RectangleBuilder::default()
                 .position(left, bottom)
@@ -146,51 +330,378 @@ impl Font {
                 .tex_coords(*tex_coords)
                 .depth(depth)
                 .build());
+        }
+
+        Ok(rectangles)
+    }
+
+    //the glyph to draw for each part of the text, its origin relative to the start of the line, and the line's width
+    #[cfg(not(feature = "shaping"))]
+    fn glyph_run(&self, text: &str, scale: f32, letter_spacing: f32) -> Result<(Vec<(u16, f32, f32)>, f32), SimpleError> {
+        let mut run = Vec::new();
+        let mut x = 0f32;
+
+        let characters = text.chars().collect::<Vec<_>>();
+        for (i, c) in characters.iter().enumerate() {
+            let glyph_index = *self.characters.get(c)
+                .ok_or(SimpleError::new("That character hasn't been loaded in this font!"))?;
+            run.push((glyph_index, x, 0f32));
+
+            x += self.advance(*c, characters.get(i + 1).copied(), scale, letter_spacing)?;
+        }
+
+        Ok((run, x))
+    }
+
+    //shapes at the raster size so the offsets scale the same way the glyph metrics do
+    #[cfg(feature = "shaping")]
+    fn glyph_run(&self, text: &str, scale: f32, letter_spacing: f32) -> Result<(Vec<(u16, f32, f32)>, f32), SimpleError> {
+        let glyphs = crate::shaping::shape(&self.font_bytes, text, self.raster_size)?;
+        Ok(crate::shaping::position_glyphs(&glyphs, self.glyph_scale(scale), letter_spacing))
+    }
+
+}
+
+//advance_width and kerning are in pixels at the font's raster size, kerning is None for the last
+//character since neither it nor letter spacing go after the end of a line
+#[cfg_attr(feature = "shaping", allow(dead_code))]
+fn glyph_advance(advance_width: f32, kerning: Option<f32>, glyph_scale: f32, letter_spacing: f32) -> f32 {
+    let mut advance = advance_width * glyph_scale;
+    if let Some(kerning) = kerning {
+        advance += letter_spacing + kerning * glyph_scale;
+    }
+
+    advance
+}
+
+//the metrics are looked up through callbacks so a line can be measured without a loaded font
+#[cfg_attr(feature = "shaping", allow(dead_code))]
+fn line_width(
+    characters: &[char], 
+    glyph_scale: f32, 
+    letter_spacing: f32, 
+    advance_width: impl Fn(char) -> Result<f32, SimpleError>, 
+    kerning: impl Fn(char, char) -> f32
+) -> Result<f32, SimpleError> {
+    let mut width = 0f32;
+    for (i, c) in characters.iter().enumerate() {
+        let kerning = characters.get(i + 1).map(|next_character| kerning(*c, *next_character));
+        width += glyph_advance(advance_width(*c)?, kerning, glyph_scale, letter_spacing);
+    }
+
+    Ok(width)
+}
+
+//greedily breaks text into lines no wider than max_width, explicit newlines are kept
+//and words wider than a whole line are broken between characters
+fn wrap_lines(text: &str, max_width: f32, measure: impl Fn(&str) -> Result<f32, SimpleError>) -> Result<Vec<String>, SimpleError> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if measure(&candidate)? <= max_width {
+                line = candidate;
+                continue
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            for c in word.chars() {
+                let mut candidate = line.clone();
+                candidate.push(c);
 
-            if let Some(next_character) = characters.get(i + 1) {
-                if let Some(kerning) = self.font.horizontal_kern(*c, *next_character, 1f32) {
-                    origin.0 += (metrics.advance_width + kerning) * scale;
-                    continue
+                if !line.is_empty() && measure(&candidate)? > max_width {
+                    lines.push(std::mem::take(&mut line));
+                    line.push(c);
+                } else {
+                    line = candidate;
                 }
             }
-            origin.0 += metrics.advance_width * scale;
         }
 
-        Ok(rectangles)
+        lines.push(line);
     }
 
+    Ok(lines)
 }
 
-pub struct TextBoxFactory {
-    material_handle: MaterialHandle,
+//where a line of the given width starts within a box
+fn line_x(alignment: TextAlignment, box_x: f32, box_width: f32, line_width: f32) -> f32 {
+    match alignment {
+        TextAlignment::Left => box_x,
+        TextAlignment::Center => box_x + (box_width - line_width) / 2f32,
+        TextAlignment::Right => box_x + box_width - line_width,
+    }
+}
+
+//where the top of a block of text sits within a box, y goes up so the top of the box is at box_y + box_height
+fn text_top(alignment: VerticalAlignment, box_y: f32, box_height: f32, text_height: f32) -> f32 {
+    match alignment {
+        VerticalAlignment::Top => box_y + box_height,
+        VerticalAlignment::Center => box_y + (box_height + text_height) / 2f32,
+        VerticalAlignment::Bottom => box_y + text_height,
+    }
+}
+
+struct CachedFont {
+    pixel_size: u32,
     font: Font,
+    material_handle: MaterialHandle,
+    last_used: Cell<u64>,
+}
+
+pub struct TextBoxFactory {
+    //the same font rasterized at different pixel sizes, each with its own atlas and material
+    fonts: Vec<CachedFont>,
+    //counts up every time a cached font is picked, so the least recently used one can be evicted
+    uses: Cell<u64>,
+    font_name: String,
+    sdf: bool,
+    pipeline_handle: PipelineHandle,
+    sampler: Sampler,
+    scale_factor: f32,
+    letter_spacing: f32,
+    alignment: TextAlignment,
+    vertical_alignment: VerticalAlignment,
+    stage: u32,
 }
 
 impl TextBoxFactory {
-    pub fn new(renderer: &mut Renderer) -> Result<Self, SimpleError> {
-        let font = Font::load(renderer,"Arial")?;
+    pub fn new(renderer: &mut Renderer) -> Result<Self, Error> {
+        Self::with_font(renderer, "Arial", false, true, include_str!("text_shader.wgsl"))
+    }
 
+    //distance field text for text that gets scaled well past the size it was rasterized at
+    pub fn new_sdf(renderer: &mut Renderer) -> Result<Self, Error> {
+        Self::with_font(renderer, "Arial", true, true, include_str!("text_sdf_shader.wgsl"))
+    }
 
-        let text_pipeline = Pipeline::load::<Vertex>(include_str!("text_shader.wgsl"))?;
-        let pipeline_handle = renderer.create_pipeline(text_pipeline);
+    //skips depth testing and draws after the rest of the scene, for huds and debug text
+    pub fn new_overlay(renderer: &mut Renderer) -> Result<Self, Error> {
+        Self::with_font(renderer, "Arial", false, false, include_str!("text_shader.wgsl"))
+    }
 
-        let material_handle = renderer.create_material(pipeline_handle)?;
-        renderer.update_material(material_handle, "t_diffuse", font.texture.clone());
+    fn with_font(renderer: &mut Renderer, font_name: &str, sdf: bool, depth_test: bool, shader: &str) -> Result<Self, Error> {
+        let text_pipeline = Pipeline::load::<Vertex>(shader)?
+            .depth_test(depth_test);
+        let pipeline_handle = renderer.create_pipeline(text_pipeline)?;
 
         let sampler = Sampler::new(renderer.create_sampler());
-        renderer.update_material(material_handle, "s_diffuse", sampler);
 
-        Ok(Self {
-            material_handle,
-            font
-        })
+        let mut factory = Self {
+            fonts: Vec::new(),
+            uses: Cell::new(0),
+            font_name: font_name.to_string(),
+            sdf,
+            pipeline_handle,
+            sampler,
+            scale_factor: 1f32,
+            letter_spacing: 0f32,
+            alignment: TextAlignment::Left,
+            vertical_alignment: VerticalAlignment::Bottom,
+            stage: if depth_test { TEXT_STAGE } else { OVERLAY_TEXT_STAGE },
+        };
+        factory.load_size(renderer, RASTER_SIZE as u32)?;
+
+        Ok(factory)
+    }
+
+    //rasterizes the font at another pixel size so text drawn near that size stays crisp
+    //distance fields scale cleanly, so sdf factories only ever keep the default size
+    pub fn cache_size(&mut self, renderer: &mut Renderer, pixel_size: u32) -> Result<(), SimpleError> {
+        if self.sdf || self.fonts.iter().any(|cached| cached.pixel_size == pixel_size) {
+            return Ok(())
+        }
+
+        self.load_size(renderer, pixel_size)?;
+
+        //text already built from an evicted size keeps drawing, its material stays with the renderer
+        if self.fonts.len() > MAX_CACHED_FONT_SIZES {
+            let least_recently_used = self.fonts.iter()
+                .enumerate()
+                .min_by_key(|(_, cached)| cached.last_used.get())
+                .map(|(i, _)| i)
+                .expect("Just cached a font");
+            self.fonts.remove(least_recently_used);
+        }
+
+        Ok(())
+    }
+
+    fn load_size(&mut self, renderer: &mut Renderer, pixel_size: u32) -> Result<(), SimpleError> {
+        let font = if self.sdf {
+            Font::load_sdf(renderer, &self.font_name)?
+        } else {
+            Font::load_sized(renderer, &self.font_name, pixel_size as f32)?
+        };
+
+        let material_handle = renderer.create_material(self.pipeline_handle)?;
+        renderer.update_material(material_handle, "t_diffuse", font.texture.clone())?;
+        renderer.update_material(material_handle, "s_diffuse", self.sampler.clone())?;
+        renderer.validate_material(material_handle)
+            .map_err(|problems| SimpleError::new(problems.join("\n")))?;
+
+        //counts as a use so a freshly cached size isn't the first one evicted
+        let last_used = Cell::new(self.next_use());
+        self.fonts.push(CachedFont { pixel_size, font, material_handle, last_used });
+        Ok(())
+    }
+
+    //physical pixels per world unit, used to pick which cached size to draw with
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+    }
+
+    fn next_use(&self) -> u64 {
+        self.uses.set(self.uses.get() + 1);
+        self.uses.get()
+    }
+
+    fn cached_font(&self, scale: f32) -> &CachedFont {
+        let pixel_size = RASTER_SIZE * scale * self.scale_factor;
+
+        let cached = self.fonts.iter()
+            .min_by(|a, b| {
+                let a_distance = (a.pixel_size as f32 - pixel_size).abs();
+                let b_distance = (b.pixel_size as f32 - pixel_size).abs();
+                a_distance.partial_cmp(&b_distance).unwrap_or(Ordering::Equal)
+            })
+            .expect("TextBoxFactory always has a font cached");
+
+        cached.last_used.set(self.next_use());
+        cached
+    }
+
+    //extra space added between each pair of characters, in world units
+    pub fn letter_spacing(mut self, letter_spacing: f32) -> Self {
+        self.letter_spacing = letter_spacing; self
+    }
+
+    pub fn set_letter_spacing(&mut self, letter_spacing: f32) {
+        self.letter_spacing = letter_spacing;
+    }
+
+    //alignments are used when laying text out in a box with create_in_box
+    pub fn alignment(mut self, alignment: TextAlignment) -> Self {
+        self.alignment = alignment; self
+    }
+
+    pub fn vertical_alignment(mut self, vertical_alignment: VerticalAlignment) -> Self {
+        self.vertical_alignment = vertical_alignment; self
+    }
+
+    pub fn font(&self, scale: f32) -> &Font { &self.cached_font(scale).font }
+
+    //wraps the text to the width of the box and aligns each line within it
+    pub fn create_in_box(&self, text: &str, position: (f32, f32), size: (f32, f32), depth: f32, scale: f32) -> Vec<(Rectangle, MaterialHandle, RenderStage)> {
+        let font = self.font(scale);
+
+        let lines = font.wrap_text(text, size.0, scale, self.letter_spacing).unwrap();
+        let line_height = font.line_height(scale);
+        let height = line_height * lines.len() as f32;
+
+        let top = text_top(self.vertical_alignment, position.1, size.1, height);
+
+        lines.iter()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let width = font.text_width(line, scale, self.letter_spacing).unwrap();
+
+                let x = line_x(self.alignment, position.0, size.0, width);
+                let y = top - line_height * (i + 1) as f32;
+
+                self.create(line, (x, y), depth, scale)
+            })
+            .collect::<Vec<_>>()
     }
 
     pub fn create(&self, text: &str, position: (f32, f32), depth: f32, scale: f32) -> Vec<(Rectangle, MaterialHandle, RenderStage)> {    
-        self.font.layout_text(text, position, scale, depth).unwrap()
+        let cached = self.cached_font(scale);
+
+        cached.font.layout_text(text, position, scale, depth, self.letter_spacing).unwrap()
             .into_iter()
-            .map(|rect| (rect, self.material_handle, RenderStage { order: 1 }))
+            .map(|rect| (rect, cached.material_handle, RenderStage { order: self.stage }))
             .collect::<Vec<_>>()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //advances and kerning pairs are in pixels at the raster size
+    fn measure(text: &str, kerning: &[((char, char), f32)], glyph_scale: f32, letter_spacing: f32) -> f32 {
+        let characters = text.chars().collect::<Vec<_>>();
+
+        line_width(&characters, glyph_scale, letter_spacing, 
+            |_| Ok(60f32),
+            |c, next_character| kerning.iter()
+                .find(|(pair, _)| *pair == (c, next_character))
+                .map(|(_, kerning)| *kerning)
+                .unwrap_or(0f32))
+            .unwrap()
+    }
+
+    #[test]
+    fn kerning_scales_with_the_glyph_metrics() {
+        let kerning = [(('A', 'V'), -8f32)];
+        //a font rasterized at 100px drawn at the default size
+        let glyph_scale = RASTER_SIZE / 100f32;
+
+        let av = measure("AV", &kerning, glyph_scale, 0f32);
+        let ax = measure("AX", &kerning, glyph_scale, 0f32);
+
+        assert_eq!(ax, 60f32);
+        assert_eq!(av, ax - 4f32);
+    }
+
+    //every character is 10 units wide
+    fn wrap(text: &str, max_width: f32) -> Vec<String> {
+        wrap_lines(text, max_width, |line| Ok(line.chars().count() as f32 * 10f32)).unwrap()
+    }
+
+    #[test]
+    fn words_wrap_onto_the_next_line() {
+        assert_eq!(wrap("the quick brown fox", 100f32), vec!["the quick", "brown fox"]);
+    }
+
+    #[test]
+    fn explicit_newlines_are_kept() {
+        assert_eq!(wrap("the\nquick brown", 100f32), vec!["the", "quick", "brown"]);
+    }
+
+    #[test]
+    fn words_wider_than_the_box_are_broken() {
+        assert_eq!(wrap("a extraordinary b", 50f32), vec!["a", "extra", "ordin", "ary b"]);
+    }
+
+    #[test]
+    fn lines_align_within_the_box() {
+        let word_width = measure("word", &[], 1f32, 0f32);
+
+        assert_eq!(line_x(TextAlignment::Left, 10f32, 400f32, word_width), 10f32);
+        assert_eq!(line_x(TextAlignment::Center, 10f32, 400f32, word_width), 10f32 + (400f32 - word_width) / 2f32);
+        assert_eq!(line_x(TextAlignment::Right, 10f32, 400f32, word_width), 10f32 + 400f32 - word_width);
+    }
+
+    #[test]
+    fn text_aligns_vertically_within_the_box() {
+        //two 20 unit lines in a box from y 100 to 200
+        assert_eq!(text_top(VerticalAlignment::Top, 100f32, 100f32, 40f32), 200f32);
+        assert_eq!(text_top(VerticalAlignment::Center, 100f32, 100f32, 40f32), 170f32);
+        assert_eq!(text_top(VerticalAlignment::Bottom, 100f32, 100f32, 40f32), 140f32);
+    }
+
+    #[test]
+    fn letter_spacing_goes_between_characters() {
+        let unspaced = measure("spacing", &[], 1f32, 0f32);
+        let spaced = measure("spacing", &[], 1f32, 3f32);
+
+        assert_eq!(spaced - unspaced, 6f32 * 3f32);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{time::{Duration, Instant}, thread};
+
+pub struct FrameLimiter {
+    target_fps: Option<u32>,
+    frame_start: Instant,
+}
+
+impl FrameLimiter {
+    //a target of None leaves the frame rate uncapped
+    pub fn new(target_fps: Option<u32>) -> Self {
+        Self {
+            target_fps,
+            frame_start: Instant::now(),
+        }
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.target_fps = target_fps;
+    }
+
+    pub fn target_fps(&self) -> Option<u32> { self.target_fps }
+
+    pub fn sleep_duration(&self, frame_time: Duration) -> Option<Duration> {
+        let target_fps = self.target_fps.filter(|fps| *fps > 0)?;
+        let frame_budget = Duration::from_secs_f64(1.0 / target_fps as f64);
+
+        frame_budget.checked_sub(frame_time)
+            .filter(|sleep| !sleep.is_zero())
+    }
+
+    //blocks until the frame budget is used up, then starts timing the next frame
+    pub fn wait(&mut self) {
+        if let Some(sleep) = self.sleep_duration(self.frame_start.elapsed()) {
+            thread::sleep(sleep);
+        }
+        self.frame_start = Instant::now();
+    }
+}
+
+//shared frame clock stored as a legion resource, delta stays zero while paused so anything driven by it stops
+pub struct Time {
+    delta: Duration,
+    elapsed: Duration,
+    last_update: Instant,
+    paused: bool,
+}
+
+impl Time {
+    pub fn new() -> Self {
+        Self {
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            last_update: Instant::now(),
+            paused: false,
+        }
+    }
+
+    //call once at the start of every frame, update_time_system does this when it's first in the schedule
+    pub fn update(&mut self) {
+        self.advance(Instant::now());
+    }
+
+    fn advance(&mut self, now: Instant) {
+        self.delta = if self.paused { Duration::ZERO } else { now - self.last_update };
+        self.elapsed += self.delta;
+        self.last_update = now;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) { self.paused = paused; }
+    pub fn paused(&self) -> bool { self.paused }
+
+    pub fn delta(&self) -> Duration { self.delta }
+    pub fn delta_seconds(&self) -> f32 { self.delta.as_secs_f32() }
+    pub fn elapsed(&self) -> Duration { self.elapsed }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[legion::system]
+pub fn update_time(#[resource] time: &mut Time) {
+    time.update();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleeps_for_the_rest_of_the_frame_budget() {
+        let limiter = FrameLimiter::new(Some(60));
+        let sleep = limiter.sleep_duration(Duration::from_millis(4)).unwrap();
+
+        let expected = Duration::from_secs_f64(1.0 / 60.0) - Duration::from_millis(4);
+        assert!(sleep.abs_diff(expected) < Duration::from_micros(1));
+        assert!(sleep.abs_diff(Duration::from_micros(12_667)) < Duration::from_micros(1));
+    }
+
+    #[test]
+    fn over_budget_frames_dont_sleep() {
+        let limiter = FrameLimiter::new(Some(60));
+        assert_eq!(limiter.sleep_duration(Duration::from_millis(20)), None);
+    }
+
+    #[test]
+    fn delta_is_the_time_between_updates() {
+        let mut time = Time::new();
+        let start = time.last_update;
+
+        time.advance(start + Duration::from_millis(10));
+        assert_eq!(time.delta(), Duration::from_millis(10));
+        assert_eq!(time.elapsed(), Duration::from_millis(10));
+
+        time.advance(start + Duration::from_millis(25));
+        assert_eq!(time.delta(), Duration::from_millis(15));
+        assert_eq!(time.elapsed(), Duration::from_millis(25));
+    }
+
+    #[test]
+    fn paused_time_stands_still() {
+        let mut time = Time::new();
+        let start = time.last_update;
+
+        time.set_paused(true);
+        time.advance(start + Duration::from_millis(10));
+        assert_eq!(time.delta(), Duration::ZERO);
+        assert_eq!(time.elapsed(), Duration::ZERO);
+
+        //the paused stretch isn't counted once time resumes
+        time.set_paused(false);
+        time.advance(start + Duration::from_millis(15));
+        assert_eq!(time.delta(), Duration::from_millis(5));
+        assert_eq!(time.elapsed(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn uncapped_frames_dont_sleep() {
+        assert_eq!(FrameLimiter::new(None).sleep_duration(Duration::from_millis(4)), None);
+        assert_eq!(FrameLimiter::new(Some(0)).sleep_duration(Duration::from_millis(4)), None);
+    }
+}
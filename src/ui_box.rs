@@ -1,21 +1,37 @@
 use regex::Regex;
 use simple_error::SimpleError;
 
-use crate::{pipeline::Pipeline, renderer::{Renderer, MaterialHandle, RenderStage}, graphics::{Rectangle, RectangleBuilder, Vertex}};
+use crate::{error::Error, pipeline::Pipeline, renderer::{Renderer, MaterialHandle, RenderStage}, graphics::{Rectangle, RectangleBuilder, Vertex}};
+
+//overlay boxes draw after the scene but before overlay text, neither is depth tested
+const BOX_STAGE: u32 = 0;
+const OVERLAY_BOX_STAGE: u32 = 2;
 
 pub struct UiBoxFactory {
-    material_handle: MaterialHandle
+    material_handle: MaterialHandle,
+    stage: u32,
 }
 
 impl UiBoxFactory {
-    pub fn new(renderer: &mut Renderer) -> Result<Self, SimpleError> {
-        let pipeline = Pipeline::load::<Vertex>(include_str!("rect.wgsl"))?;
+    pub fn new(renderer: &mut Renderer) -> Result<Self, Error> {
+        Self::with_depth_test(renderer, true)
+    }
+
+    //skips depth testing and draws after the rest of the scene, for huds and debug panels
+    pub fn new_overlay(renderer: &mut Renderer) -> Result<Self, Error> {
+        Self::with_depth_test(renderer, false)
+    }
+
+    fn with_depth_test(renderer: &mut Renderer, depth_test: bool) -> Result<Self, Error> {
+        let pipeline = Pipeline::load::<Vertex>(include_str!("rect.wgsl"))?
+            .depth_test(depth_test);
         // renderer
-        let pipeline_handle = renderer.create_pipeline(pipeline);
+        let pipeline_handle = renderer.create_pipeline(pipeline)?;
         let material_handle = renderer.create_material(pipeline_handle)?;
 
         Ok(Self {
-            material_handle
+            material_handle,
+            stage: if depth_test { BOX_STAGE } else { OVERLAY_BOX_STAGE },
         })
     }
 
@@ -32,7 +48,80 @@ impl UiBoxFactory {
             .depth(depth)
             .build();
 
-        Ok((rectangle, self.material_handle, RenderStage { order: 0 }))
+        Ok((rectangle, self.material_handle, RenderStage { order: self.stage }))
+    }
+
+    //a vertical gradient from the top color down to the bottom color
+    pub fn create_gradient(&self, color_top: &str, color_bottom: &str, position: (f32, f32), size: (f32, f32), depth: f32) 
+        -> Result<(Rectangle, MaterialHandle, RenderStage), SimpleError> 
+    {
+        let rectangle = RectangleBuilder::default()
+            .position(position.0, position.1)
+            .size(size.0, size.1)
+            .color_top(hex_color(color_top)?)
+            .color_bottom(hex_color(color_bottom)?)
+            .depth(depth)
+            .build();
+
+        Ok((rectangle, self.material_handle, RenderStage { order: self.stage }))
+    }
+}
+
+//border widths are in texture pixels and are drawn at that size, only the edges and center stretch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NineSlice {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl NineSlice {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self { left, right, top, bottom }
+    }
+
+    //returns the slices bottom row first, left to right, spawn each with the textured material
+    pub fn rectangles(&self, texture_size: (f32, f32), position: (f32, f32), size: (f32, f32), depth: f32, color: [f32; 3]) 
+        -> [Rectangle; 9] 
+    {
+        //borders shrink evenly if the box is smaller than them
+        let x_scale = (size.0 / (self.left + self.right)).min(1.0);
+        let y_scale = (size.1 / (self.top + self.bottom)).min(1.0);
+
+        let xs = [
+            position.0, 
+            position.0 + self.left * x_scale, 
+            position.0 + size.0 - self.right * x_scale, 
+            position.0 + size.0
+        ];
+        let ys = [
+            position.1, 
+            position.1 + self.bottom * y_scale, 
+            position.1 + size.1 - self.top * y_scale, 
+            position.1 + size.1
+        ];
+
+        let us = [0.0, self.left / texture_size.0, 1.0 - self.right / texture_size.0, 1.0];
+        //texture v runs top to bottom while y runs bottom to top
+        let vs = [1.0, 1.0 - self.bottom / texture_size.1, self.top / texture_size.1, 0.0];
+
+        std::array::from_fn(|i| {
+            let (column, row) = (i % 3, i / 3);
+
+            RectangleBuilder::default()
+                .position(xs[column], ys[row])
+                .size(xs[column + 1] - xs[column], ys[row + 1] - ys[row])
+                .color(color)
+                .depth(depth)
+                .tex_coords([
+                    [us[column],     vs[row]], 
+                    [us[column],     vs[row + 1]], 
+                    [us[column + 1], vs[row]], 
+                    [us[column + 1], vs[row + 1]]
+                ])
+                .build()
+        })
     }
 }
 
@@ -61,4 +150,50 @@ pub fn hex_color(color: &str) -> Result<[f32; 3], SimpleError> {
 
     println!("{}, {}, {}", r, g, b);
     Ok([ r / 255f32, g / 255f32, b / 255f32 ])
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEPTH: f32 = 0.5;
+    const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+
+    //expected vertices in the order RectangleBuilder emits them
+    fn quad(x: (f32, f32), y: (f32, f32), u: (f32, f32), v: (f32, f32)) -> [Vertex; 4] {
+        [
+            Vertex::new([x.0, y.0, DEPTH], WHITE, [u.0, v.0]),
+            Vertex::new([x.0, y.1, DEPTH], WHITE, [u.0, v.1]),
+            Vertex::new([x.1, y.0, DEPTH], WHITE, [u.1, v.0]),
+            Vertex::new([x.1, y.1, DEPTH], WHITE, [u.1, v.1]),
+        ]
+    }
+
+    #[test]
+    fn slices_keep_borders_at_texture_size() {
+        let slices = NineSlice::new(10.0, 10.0, 10.0, 10.0)
+            .rectangles((40.0, 40.0), (100.0, 200.0), (100.0, 60.0), DEPTH, WHITE);
+
+        //bottom left corner
+        assert_eq!(slices[0].vertices, quad((100.0, 110.0), (200.0, 210.0), (0.0, 0.25), (1.0, 0.75)));
+        //bottom edge stretches horizontally
+        assert_eq!(slices[1].vertices, quad((110.0, 190.0), (200.0, 210.0), (0.25, 0.75), (1.0, 0.75)));
+        //left edge stretches vertically
+        assert_eq!(slices[3].vertices, quad((100.0, 110.0), (210.0, 250.0), (0.0, 0.25), (0.75, 0.25)));
+        //center stretches both ways
+        assert_eq!(slices[4].vertices, quad((110.0, 190.0), (210.0, 250.0), (0.25, 0.75), (0.75, 0.25)));
+        //top right corner
+        assert_eq!(slices[8].vertices, quad((190.0, 200.0), (250.0, 260.0), (0.75, 1.0), (0.25, 0.0)));
+    }
+
+    #[test]
+    fn borders_shrink_in_boxes_smaller_than_them() {
+        let slices = NineSlice::new(10.0, 10.0, 10.0, 10.0)
+            .rectangles((40.0, 40.0), (100.0, 200.0), (10.0, 30.0), DEPTH, WHITE);
+
+        //only the axis that's too small shrinks, and the uvs still cover the whole border
+        assert_eq!(slices[0].vertices, quad((100.0, 105.0), (200.0, 210.0), (0.0, 0.25), (1.0, 0.75)));
+        assert_eq!(slices[4].vertices, quad((105.0, 105.0), (210.0, 220.0), (0.25, 0.75), (0.75, 0.25)));
+        assert_eq!(slices[8].vertices, quad((105.0, 110.0), (220.0, 230.0), (0.75, 1.0), (0.25, 0.0)));
+    }
+}
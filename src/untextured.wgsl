@@ -11,12 +11,15 @@ struct VertexOutput {
     @location(0) color: vec3<f32>,
 };
 
+@group(0) @binding(0)
+var<uniform> view_proj: mat4x4<f32>;
+
 @vertex
 fn vs_main(
     model: VertexInput,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.clip_position = view_proj * vec4<f32>(model.position, 1.0);
     out.color = model.color;
     return out;
 }